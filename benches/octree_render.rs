@@ -23,9 +23,30 @@ fn bench_group(c: &mut Criterion) {
     }
 }
 
+/// Renders the same sphere with a growing number of threads to check construction scales.
+#[cfg(feature = "parallel")]
+fn bench_threads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("octree_render_threads");
+    let csg_func = CsgFunc::new(Box::new(move |x, y, z| {
+        ((0.0 - z).powi(2) + (0.0 - x).powi(2) + (0.0 - y).powi(2)).sqrt() - RADIUS
+    }));
+    let resolution = BOUND / 2.0_f32.powi(7);
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = std::iter::successors(Some(1), |n| Some(n * 2)).take_while(|&n| n <= max_threads);
+    for threads in threads {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &resolution, |b, s| {
+            pool.install(|| b.iter(|| sphere_shape(BOUND / 2.0, black_box(*s), &csg_func)))
+        });
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn bench_threads(_c: &mut Criterion) {}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_group
+    targets = bench_group, bench_threads
 }
 criterion_main!(benches);
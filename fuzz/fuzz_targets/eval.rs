@@ -0,0 +1,22 @@
+#![no_main]
+
+use {conjure::lang, libfuzzer_sys::fuzz_target};
+
+// Reading and evaluating any source gives a value or an error, never a panic
+fuzz_target!(|source: &str| {
+    if let Ok(forms) = lang::Reader::read_all_spanned(source) {
+        let env = lang::Env::new();
+        // Files such as /dev/zero would hang or exhaust memory
+        env.forbid_includes();
+        for (sym, func) in lang::Namespace::new() {
+            env.register_sym(sym, func);
+        }
+        for (ty, spans) in forms {
+            let _ = format!("{:?} {}", ty, ty);
+            if let Ok(ty) = lang::eval_spanned(ty, &spans, &env) {
+                let _ = format!("{:?} {}", ty, ty);
+            }
+        }
+        env.clear();
+    }
+});
@@ -1,12 +1,22 @@
 use {
     crate::{
-        octree::{OctAxis, OctantIdx, Octree},
-        types::{Face, Point},
+        octree::{Feature, Features, HermitePoint, OctAxis, Octant, OctantIdx, Octree},
+        progress::{Stage, Tally},
+        qef::Qef,
+        types::{Aabb, Face, Point},
         CsgFunc,
     },
-    nalgebra::{linalg::SVD, MatrixXx1, MatrixXx3, RowVector1, RowVector3, Vector3},
+    nalgebra::Vector3,
+    smallvec::SmallVec,
 };
 
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Levels below the root where `cell_proc` splits the extraction over threads.
+#[cfg(feature = "parallel")]
+const PARALLEL_LEVELS: u8 = 3;
+
 /*
  *  Our coordinate system
  *
@@ -36,17 +46,15 @@ use {
 
 /// Locate where the isosurface intersects the line between `p1` and `p2`.
 ///
+/// `v1` and `v2` are the values of the shape at `p1` and `p2`, they are not sampled again.
 /// To effectivly use this, one axis of the cube should be held constant
-fn find_point_on_edge(p1: Point, p2: Point, func: &CsgFunc) -> Option<Point> {
-    if func.call_point(p1) > func.call_point(p2) {
+fn find_point_on_edge(p1: Point, v1: f32, p2: Point, v2: f32, func: &CsgFunc) -> Option<Point> {
+    if v1 > v2 {
         // if p1 is bigger than 0, swap the direction of the points
-        return find_point_on_edge(p2, p1, func);
+        return find_point_on_edge(p2, v2, p1, v1, func);
     }
-    // // If there isn't a sign change, we don't have any point on this edge
-    if func.call_point(p1) < 0.0 && func.call_point(p2) < 0.0 {
-        return None;
-    }
-    if func.call_point(p1) >= 0.0 && func.call_point(p2) >= 0.0 {
+    // If there isn't a sign change, we don't have any point on this edge
+    if v1 >= 0.0 || v2 < 0.0 {
         return None;
     }
     // Binary search along the side
@@ -76,84 +84,340 @@ fn find_point_on_edge(p1: Point, p2: Point, func: &CsgFunc) -> Option<Point> {
     Some(Point::new(x_val, y_val, z_val))
 }
 
-/// Find a point in the cell that minimizes the error from the normals
-pub fn new_feature(
+/// Edges of a cell as pairs of corners, grouped by the axis they run along (x, y then z).
+///
+/// Corners are numbered like the children of an `Octant`: bit 2 selects the upper x,
+/// bit 1 the lower y and bit 0 the upper z.
+pub static EDGES: [(usize, usize); 12] = [
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+];
+
+/// Faces of a cell, as the corner bit held constant on the face and its value.
+static FACES: [(usize, usize); 6] = [(4, 0), (4, 4), (2, 0), (2, 2), (1, 0), (1, 1)];
+
+/// Position of a point on the 3x3x3 lattice formed by the corners of the children of a cell.
+///
+/// Each coordinate goes from the side of the cell where the matching corner bit is clear (0),
+/// through the middle (1), to the side where it is set (2).
+pub fn lattice_point(x_axis: OctAxis, y_axis: OctAxis, z_axis: OctAxis, at: [usize; 3]) -> Point {
+    Point::new(
+        [x_axis.lower, x_axis.center(), x_axis.upper][at[0]],
+        [y_axis.upper, y_axis.center(), y_axis.lower][at[1]],
+        [z_axis.lower, z_axis.center(), z_axis.upper][at[2]],
+    )
+}
+
+/// Index of a lattice point in the values returned by `sample_lattice`.
+fn lattice_idx(at: [usize; 3]) -> usize {
+    at[0] * 9 + at[1] * 3 + at[2]
+}
+
+/// Lattice coordinates of `corner` of the child `child`, use `child = corner` for the corners of
+/// the cell itself.
+fn corner_lattice(child: usize, corner: usize) -> [usize; 3] {
+    [2, 1, 0].map(|bit| (child >> bit & 1) + (corner >> bit & 1))
+}
+
+/// Position of `corner` in the cell.
+pub fn corner_point(x_axis: OctAxis, y_axis: OctAxis, z_axis: OctAxis, corner: usize) -> Point {
+    lattice_point(x_axis, y_axis, z_axis, corner_lattice(corner, corner))
+}
+
+/// Samples the shape at every corner of the cell.
+pub fn sample_corners(
     x_axis: OctAxis,
     y_axis: OctAxis,
     z_axis: OctAxis,
-    shape_func: &CsgFunc,
-) -> Option<Point> {
-    let mut points: smallvec::SmallVec<[Point; 12]> = [
-        // front left vertical
-        ((x_axis.upper, y_axis.lower, z_axis.lower), (x_axis.upper, y_axis.upper, z_axis.lower)),
-        // front right vertical
-        ((x_axis.lower, y_axis.lower, z_axis.lower), (x_axis.lower, y_axis.upper, z_axis.lower)),
-        // front bottom horizontal
-        ((x_axis.lower, y_axis.lower, z_axis.lower), (x_axis.upper, y_axis.lower, z_axis.lower)),
-        // front top horizontal
-        ((x_axis.lower, y_axis.upper, z_axis.lower), (x_axis.upper, y_axis.upper, z_axis.lower)),
-        // back left vertical
-        ((x_axis.upper, y_axis.lower, z_axis.upper), (x_axis.upper, y_axis.upper, z_axis.upper)),
-        // back right vertical
-        ((x_axis.lower, y_axis.lower, z_axis.upper), (x_axis.lower, y_axis.upper, z_axis.upper)),
-        // back bottom horizontal
-        ((x_axis.lower, y_axis.lower, z_axis.upper), (x_axis.upper, y_axis.lower, z_axis.upper)),
-        // back top horizontal
-        ((x_axis.lower, y_axis.upper, z_axis.upper), (x_axis.upper, y_axis.upper, z_axis.upper)),
-        // right top side
-        ((x_axis.upper, y_axis.upper, z_axis.lower), (x_axis.upper, y_axis.upper, z_axis.upper)),
-        // left top side
-        ((x_axis.lower, y_axis.upper, z_axis.lower), (x_axis.lower, y_axis.upper, z_axis.upper)),
-        // left bottom side
-        ((x_axis.lower, y_axis.lower, z_axis.lower), (x_axis.lower, y_axis.lower, z_axis.upper)),
-        // right bottom side
-        ((x_axis.upper, y_axis.lower, z_axis.lower), (x_axis.upper, y_axis.lower, z_axis.upper)),
-    ]
-    .iter()
-    .filter_map(|(p0, p1)| {
-        find_point_on_edge(Point::new(p0.0, p0.1, p0.2), Point::new(p1.0, p1.1, p1.2), &shape_func)
-    })
-    .collect();
-
-    if points.len() >= 2 {
-        let mut normals: Vec<Vector3<f32>> =
-            points.iter().map(|p| shape_func.normal(p.x, p.y, p.z)).collect();
-
-        let mean_x = points.iter().fold(0.0, |accum, p| p.x + accum) / points.len() as f32;
-        let mean_y = points.iter().fold(0.0, |accum, p| p.y + accum) / points.len() as f32;
-        let mean_z = points.iter().fold(0.0, |accum, p| p.z + accum) / points.len() as f32;
-        // Add some weak bias to keeping the point within the cell
-        let strength = 0.12;
-        points.push(Point::new(mean_x, mean_y, mean_z));
-        points.push(Point::new(mean_x, mean_y, mean_z));
-        points.push(Point::new(mean_x, mean_y, mean_z));
-        normals.push(Vector3::new(strength, 0.0, 0.0));
-        normals.push(Vector3::new(0.0, strength, 0.0));
-        normals.push(Vector3::new(0.0, 0.0, strength));
-
-        let mut rows = vec![];
-        for n in &normals {
-            rows.push(RowVector3::new(n.x, n.y, n.z));
+    func: &CsgFunc,
+) -> [f32; 8] {
+    let mut corners = [0.0; 8];
+    for (corner, value) in corners.iter_mut().enumerate() {
+        *value = func.call_point(corner_point(x_axis, y_axis, z_axis, corner));
+    }
+    corners
+}
+
+/// Samples the shape on the lattice of the cell, reusing the already known `corners`.
+pub fn sample_lattice(
+    x_axis: OctAxis,
+    y_axis: OctAxis,
+    z_axis: OctAxis,
+    corners: [f32; 8],
+    func: &CsgFunc,
+) -> [f32; 27] {
+    let mut lattice = [0.0; 27];
+    for (idx, value) in lattice.iter_mut().enumerate() {
+        let at = [idx / 9, idx / 3 % 3, idx % 3];
+        *value = if at.iter().all(|&c| c != 1) {
+            corners[(at[0] / 2) << 2 | (at[1] / 2) << 1 | (at[2] / 2)]
+        } else {
+            func.call_point(lattice_point(x_axis, y_axis, z_axis, at))
+        };
+    }
+    lattice
+}
+
+/// Values at the corners of the child `child`, taken from the lattice of its parent.
+pub fn child_corners(lattice: &[f32; 27], child: usize) -> [f32; 8] {
+    let mut corners = [0.0; 8];
+    for (corner, value) in corners.iter_mut().enumerate() {
+        *value = lattice[lattice_idx(corner_lattice(child, corner))];
+    }
+    corners
+}
+
+/// Bitmask with a bit set for every corner within the shape.
+pub fn inside_mask(corners: &[f32; 8]) -> u8 {
+    corners.iter().enumerate().fold(
+        0,
+        |inside, (corner, &value)| {
+            if value < 0.0 {
+                inside | 1 << corner
+            } else {
+                inside
+            }
+        },
+    )
+}
+
+/// Pairs up the edges with a sign change on every face of a cell.
+///
+/// `inside` has a bit set for every corner within the shape. A face with two inside corners on a
+/// diagonal keeps those corners apart. Since that choice only depends on the face, neighbouring
+/// cells always agree and the mesh stays manifold.
+fn face_pairs(inside: u8) -> SmallVec<[(usize, usize); 12]> {
+    let crossed = |edge: usize| {
+        let (c0, c1) = EDGES[edge];
+        (inside >> c0 ^ inside >> c1) & 1 == 1
+    };
+
+    let mut pairs = SmallVec::new();
+    for (bit, value) in FACES {
+        let face_edges: SmallVec<[usize; 4]> = (0..12)
+            .filter(|&e| crossed(e) && EDGES[e].0 & bit == value && EDGES[e].1 & bit == value)
+            .collect();
+        match face_edges[..] {
+            [e0, e1] => pairs.push((e0, e1)),
+            [..] => {
+                // Ambiguous face, join the two crossed edges around each inside corner
+                for corner in (0..8).filter(|c| c & bit == value && inside & (1 << c) != 0) {
+                    let around: SmallVec<[usize; 2]> = face_edges
+                        .iter()
+                        .copied()
+                        .filter(|&e| EDGES[e].0 == corner || EDGES[e].1 == corner)
+                        .collect();
+                    if let [e0, e1] = around[..] {
+                        pairs.push((e0, e1));
+                    }
+                }
+            }
         }
-        let a = MatrixXx3::from_rows(&rows);
+    }
+    pairs
+}
 
-        let mut b_rows = vec![];
-        for (p, norm) in points.iter().zip(normals.iter()) {
-            b_rows.push(RowVector1::new((p.x * norm.x) + (p.y * norm.y) + (p.z * norm.z)));
+/// Groups the edges with a sign change into the separate sheets of surface crossing a cell.
+///
+/// `inside` has a bit set for every corner within the shape, see `face_pairs` for how the
+/// edges are connected. Returns a bitmask of `EDGES` for every sheet.
+pub fn surface_components(inside: u8) -> SmallVec<[u16; 4]> {
+    // Union-find over the edges of the cell
+    let mut parent: [usize; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+    fn find(parent: &mut [usize; 12], edge: usize) -> usize {
+        let mut root = edge;
+        while parent[root] != root {
+            root = parent[root];
         }
+        parent[edge] = root;
+        root
+    }
 
-        let b = MatrixXx1::from_rows(&b_rows);
-        let svd = SVD::new(a, true, true);
-        let solution = svd.solve(&b, 0.0).unwrap();
+    for (e0, e1) in face_pairs(inside) {
+        let root = find(&mut parent, e0);
+        let other = find(&mut parent, e1);
+        parent[root] = other;
+    }
 
-        let x = solution.data.0[0][0];
-        let y = solution.data.0[0][1];
-        let z = solution.data.0[0][2];
+    let mut components: SmallVec<[(usize, u16); 4]> = SmallVec::new();
+    let crossed = |edge: usize| {
+        let (c0, c1) = EDGES[edge];
+        (inside >> c0 ^ inside >> c1) & 1 == 1
+    };
+    for edge in (0..12).filter(|&e| crossed(e)) {
+        let root = find(&mut parent, edge);
+        match components.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, edges)) => *edges |= 1 << edge,
+            None => components.push((root, 1 << edge)),
+        }
+    }
+    components.into_iter().map(|(_, edges)| edges).collect()
+}
 
-        Some(Point::new(x, y, z))
-    } else {
-        None
+/// Orders the edges of one sheet of surface (from `surface_components`) into the loop going
+/// around its polygon.
+///
+/// The loop winds counter-clockwise when seen from outside of the shape.
+pub fn edge_loop(inside: u8, edges: u16) -> SmallVec<[usize; 12]> {
+    let pairs: SmallVec<[(usize, usize); 12]> =
+        face_pairs(inside).into_iter().filter(|&(e0, _)| edges & (1 << e0) != 0).collect();
+
+    let mut edge_loop: SmallVec<[usize; 12]> = SmallVec::new();
+    let mut used = [false; 12];
+    let mut current = pairs.first().map(|&(e0, _)| e0);
+    while let Some(edge) = current {
+        edge_loop.push(edge);
+        current = None;
+        for (i, &(e0, e1)) in pairs.iter().enumerate() {
+            if !used[i] && (e0 == edge || e1 == edge) {
+                used[i] = true;
+                current = Some(if e0 == edge { e1 } else { e0 });
+                break;
+            }
+        }
+        if current == edge_loop.first().copied() {
+            break;
+        }
+    }
+
+    // Compare the winding of the loop through the edge midpoints with the direction the edges
+    // leave the shape in
+    let position = |corner: usize| {
+        Vector3::new(corner >> 2 & 1, !corner >> 1 & 1, corner & 1).map(|c| c as f32)
+    };
+    let midpoint = |edge: usize| (position(EDGES[edge].0) + position(EDGES[edge].1)) / 2.0;
+    let winding: Vector3<f32> = edge_loop
+        .iter()
+        .zip(edge_loop.iter().cycle().skip(1))
+        .map(|(&e0, &e1)| midpoint(e0).cross(&midpoint(e1)))
+        .sum();
+    let outward: Vector3<f32> = edge_loop
+        .iter()
+        .map(|&edge| {
+            let (c0, c1) = EDGES[edge];
+            match inside & (1 << c0) != 0 {
+                true => position(c1) - position(c0),
+                false => position(c0) - position(c1),
+            }
+        })
+        .sum();
+    if winding.dot(&outward) < 0.0 {
+        edge_loop.reverse();
+    }
+    edge_loop
+}
+
+/// Locate the surface crossing on every edge of the cell with a sign change.
+pub fn hermite_edges(
+    x_axis: OctAxis,
+    y_axis: OctAxis,
+    z_axis: OctAxis,
+    corners: [f32; 8],
+    shape_func: &CsgFunc,
+) -> [Option<HermitePoint>; 12] {
+    let mut hermite = [None; 12];
+    for (edge, &(c0, c1)) in EDGES.iter().enumerate() {
+        let p0 = corner_point(x_axis, y_axis, z_axis, c0);
+        let p1 = corner_point(x_axis, y_axis, z_axis, c1);
+        hermite[edge] =
+            find_point_on_edge(p0, corners[c0], p1, corners[c1], shape_func).map(|point| {
+                HermitePoint { point, normal: shape_func.normal(point.x, point.y, point.z) }
+            });
+    }
+    hermite
+}
+
+/// Collect the hermite data of the cell `edges` into a `Qef`.
+fn edges_qef(hermite: &[Option<HermitePoint>; 12], edges: u16) -> Qef {
+    let mut qef = Qef::new();
+    for (edge, hermite) in hermite.iter().enumerate() {
+        if let (true, Some(h)) = (edges & (1 << edge) != 0, hermite) {
+            qef.add(h.point, h.normal);
+        }
+    }
+    qef
+}
+
+/// Find a feature point for every sheet of surface crossing the cell.
+pub fn features_from_hermite(
+    x_axis: OctAxis,
+    y_axis: OctAxis,
+    z_axis: OctAxis,
+    inside: u8,
+    hermite: &[Option<HermitePoint>; 12],
+) -> Features {
+    surface_components(inside)
+        .into_iter()
+        .filter_map(|edges| {
+            let qef = edges_qef(hermite, edges);
+            let (point, _) = feature_from_qef(&qef, x_axis, y_axis, z_axis)?;
+            Some(Feature { point, edges, qef })
+        })
+        .collect()
+}
+
+/// Solves the `Qef` of a cell, returning the feature point and its residual error.
+///
+/// Cells with less than two edge intersections have no feature. If the solution lands outside
+/// of the cell it is clamped back to the cell bounds to avoid spikes in the mesh.
+pub fn feature_from_qef(
+    qef: &Qef,
+    x_axis: OctAxis,
+    y_axis: OctAxis,
+    z_axis: OctAxis,
+) -> Option<(Point, f32)> {
+    if qef.len() < 2 {
+        return None;
+    }
+
+    let (point, error) = qef.solve();
+    if x_axis.contains(point.x) && y_axis.contains(point.y) && z_axis.contains(point.z) {
+        return Some((point, error));
     }
+
+    let point = Point::new(x_axis.clamp(point.x), y_axis.clamp(point.y), z_axis.clamp(point.z));
+    Some((point, qef.error(point)))
+}
+
+/// Checks that collapsing the cell into a single leaf preserves the topology of the surface.
+///
+/// The sign at the middle of every edge, face and of the cell itself must agree with the sign
+/// of at least one of the corners it lies between. `lattice` comes from `sample_lattice`.
+pub fn topology_safe(lattice: &[f32; 27]) -> bool {
+    let inside = |at: [usize; 3]| lattice[lattice_idx(at)] < 0.0;
+
+    for idx in 0..27 {
+        let mid = [idx / 9, idx / 3 % 3, idx % 3];
+        if mid.iter().all(|&c| c != 1) {
+            // A corner of the cell, nothing to compare against
+            continue;
+        }
+        let sign = inside(mid);
+        // Every corner reachable by moving the middle coordinates to either end
+        let agrees = (0..8).any(|corner: usize| {
+            let mut point = mid;
+            for (axis, c) in point.iter_mut().enumerate() {
+                if *c == 1 {
+                    *c = if corner & (1 << axis) == 0 { 0 } else { 2 };
+                }
+            }
+            inside(point) == sign
+        });
+        if !agrees {
+            return false;
+        }
+    }
+    true
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -232,41 +496,134 @@ static FACE_FACE_MAP: [[(usize, usize); 4]; 3] = [
     [(5, 1), (4, 0), (7, 3), (6, 2)],
 ];
 
+/// Picks where the vertex of a `Feature` is placed in the mesh.
+pub type Placement = fn(&Feature) -> Point;
+
 /// Entry point to the dual contour face extraction
-pub fn cell_proc(tree: &Octree, idx: OctantIdx) -> Vec<Face> {
-    if tree.get_octant(idx).is_leaf() {
-        return vec![];
-    }
+///
+/// `placement` positions the vertex of every cell, dual contouring uses the feature point.
+/// With the `parallel` feature the top of the octree is processed on multiple threads, the faces
+/// always come out in the same order. Progress is reported to the progress of the octree, once
+/// it is cancelled the faces are incomplete.
+pub fn cell_proc(tree: &Octree, idx: OctantIdx, placement: Placement) -> Vec<Face> {
+    cell_proc_in(tree, idx, placement, None).into_iter().map(|(face, _)| face).collect()
+}
 
+/// Extracts the faces built from cells whose combined box overlaps `region`, along with that box,
+/// like `cell_proc`. Subtrees clear of the region are skipped. Every face is extracted without a
+/// region.
+pub fn cell_proc_in(
+    tree: &Octree,
+    idx: OctantIdx,
+    placement: Placement,
+    region: Option<&Aabb>,
+) -> Vec<(Face, Aabb)> {
+    tree.progress().start(Stage::Extracting);
     let mut faces = vec![];
-    // Since it has children, spawn 8 calls to cell_proc
-    for child_idx in &tree.get_octant(idx).children.unwrap() {
-        faces.extend(cell_proc(tree, *child_idx));
-    }
-    let children = tree.get_octant(idx).children.unwrap();
+    cell_faces(tree, idx, placement, region, 0, &mut tree.progress().tally(), &mut faces);
+    faces
+}
+
+/// Box around `cells`.
+fn cells_bounds(tree: &Octree, cells: &[OctantIdx]) -> Aabb {
+    cells
+        .iter()
+        .map(|&cell| tree.get_octant(cell).bounds())
+        .reduce(|a, b| a.union(&b))
+        .expect("faces are built from at least one cell")
+}
 
-    // call face_proc on every set of two cells that share a face
-    for (c0, c1, dir) in &CELL_FACE_MAP {
-        faces.extend(face_proc(tree, *dir, [children[*c0], children[*c1]]));
+/// Returns a bool based on if faces built from `cells` can reach into `region`.
+fn in_region(tree: &Octree, cells: &[OctantIdx], region: Option<&Aabb>) -> bool {
+    match region {
+        Some(region) => cells_bounds(tree, cells).overlaps(region),
+        None => true,
     }
+}
 
-    // call edge_proc on every set of four subcells that share an edge
-    for (c0, c1, c2, c3, dir) in &CELL_EDGE_MAP {
-        faces.extend(edge_proc(
-            tree,
-            *dir,
-            [children[*c0], children[*c1], children[*c2], children[*c3]],
-        ));
+/// One of the recursive calls made by `cell_faces`.
+#[derive(Clone, Copy)]
+enum Proc {
+    Cell(OctantIdx),
+    Face(TreeAxis, [OctantIdx; 2]),
+    Edge(TreeAxis, [OctantIdx; 4]),
+}
+
+/// Recursive function that extracts the faces within a single octant.
+///
+/// `level` is the depth of the octant below the root of the extraction. Visited octants are
+/// counted in `tally`, subtrees extracted on other threads count in tallies of their own.
+fn cell_faces(
+    tree: &Octree,
+    idx: OctantIdx,
+    placement: Placement,
+    region: Option<&Aabb>,
+    level: u8,
+    tally: &mut Tally,
+    faces: &mut Vec<(Face, Aabb)>,
+) {
+    let children = match tree.get_octant(idx).children {
+        Some(children) if in_region(tree, &[idx], region) && !tally.progress().is_cancelled() => {
+            tally.visit(0.0);
+            children
+        }
+        // Nothing left to do within the octant
+        _ => return tally.visit(0.125_f64.powi(level as i32)),
+    };
+
+    // Since it has children, spawn 8 calls to cell_proc, then call face_proc on every set of two
+    // cells that share a face and edge_proc on every set of four subcells that share an edge
+    let procs = children
+        .iter()
+        .map(|&child| Proc::Cell(child))
+        .chain(
+            CELL_FACE_MAP
+                .iter()
+                .map(|&(c0, c1, dir)| Proc::Face(dir, [children[c0], children[c1]])),
+        )
+        .chain(CELL_EDGE_MAP.iter().map(|&(c0, c1, c2, c3, dir)| {
+            Proc::Edge(dir, [children[c0], children[c1], children[c2], children[c3]])
+        }));
+    let run = |proc: Proc, tally: &mut Tally, faces: &mut Vec<(Face, Aabb)>| match proc {
+        Proc::Cell(idx) => cell_faces(tree, idx, placement, region, level + 1, tally, faces),
+        Proc::Face(dir, cells) => face_proc(tree, dir, cells, placement, region, faces),
+        Proc::Edge(dir, cells) => edge_proc(tree, dir, cells, placement, region, faces),
+    };
+
+    // Every call fills a buffer of its own, joined in order so the result does not depend on
+    // how the work was scheduled
+    #[cfg(feature = "parallel")]
+    if level < PARALLEL_LEVELS {
+        let procs: Vec<Proc> = procs.collect();
+        let buffers: Vec<Vec<(Face, Aabb)>> = procs
+            .into_par_iter()
+            .map(|proc| {
+                let mut buffer = vec![];
+                run(proc, &mut tree.progress().tally(), &mut buffer);
+                buffer
+            })
+            .collect();
+        faces.extend(buffers.into_iter().flatten());
+        return;
     }
 
-    faces
+    procs.for_each(|proc| run(proc, tally, faces));
 }
 
 /// Recursive function that extracts faces from two octants sharing a common face.
 ///
 /// Calls itself 4 times with every pair of cells in direction `dir` that share a face.
-fn face_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 2]) -> Vec<Face> {
-    let mut faces = vec![];
+fn face_proc(
+    tree: &Octree,
+    dir: TreeAxis,
+    cells: [OctantIdx; 2],
+    placement: Placement,
+    region: Option<&Aabb>,
+    faces: &mut Vec<(Face, Aabb)>,
+) {
+    if !in_region(tree, &cells, region) {
+        return;
+    }
     if tree.get_octant(cells[0]).children.is_some() || tree.get_octant(cells[1]).children.is_some()
     {
         for (c0, c1) in FACE_FACE_MAP[dir as usize].iter() {
@@ -278,7 +635,7 @@ fn face_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 2]) -> Vec<Face> {
                 Some(children) => children[*c1],
                 None => cells[1],
             };
-            faces.extend(face_proc(tree, dir, [o0, o1]));
+            face_proc(tree, dir, [o0, o1], placement, region, faces);
         }
 
         for (c0, c1, c2, c3, edge_dir, order) in FACE_EDGE_MAP[dir as usize].iter() {
@@ -299,19 +656,26 @@ fn face_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 2]) -> Vec<Face> {
                 None => cells[CELL_MAP[*order][3]],
             };
 
-            faces.extend(edge_proc(tree, *edge_dir, [o0, o1, o2, o3]));
+            edge_proc(tree, *edge_dir, [o0, o1, o2, o3], placement, region, faces);
         }
     }
-
-    faces
 }
 
 /// Recursive function that extracts faces from four edge-adjacent octants.
 ///
 /// Calls itself twice in the direction `dir` for all four sub-cells
 /// that share a half-edge contained in the edge.
-fn edge_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 4]) -> Vec<Face> {
-    let mut faces = vec![];
+fn edge_proc(
+    tree: &Octree,
+    dir: TreeAxis,
+    cells: [OctantIdx; 4],
+    placement: Placement,
+    region: Option<&Aabb>,
+    faces: &mut Vec<(Face, Aabb)>,
+) {
+    if !in_region(tree, &cells, region) {
+        return;
+    }
     match (
         tree.get_octant(cells[0]).children,
         tree.get_octant(cells[1]).children,
@@ -319,8 +683,8 @@ fn edge_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 4]) -> Vec<Face> {
         tree.get_octant(cells[3]).children,
     ) {
         (None, None, None, None) => {
-            if let Some(face) = make_face(tree, cells) {
-                faces.push(face);
+            if let Some(face) = make_face(tree, cells, placement) {
+                faces.push((face, cells_bounds(tree, &cells)));
             }
         }
         (o0, o1, o2, o3) => {
@@ -341,46 +705,172 @@ fn edge_proc(tree: &Octree, dir: TreeAxis, cells: [OctantIdx; 4]) -> Vec<Face> {
                     Some(o3) => o3[*idx3],
                     None => cells[3],
                 };
-                faces.extend(edge_proc(tree, dir, [c0, c1, c2, c3]));
+                edge_proc(tree, dir, [c0, c1, c2, c3], placement, region, faces);
             }
         }
     }
-    faces
 }
 
-/// Creates a face of the polygon if all leaf cells have a feature.
-fn make_face(tree: &Octree, cells: [OctantIdx; 4]) -> Option<Face> {
+/// Where the shared edge lies on a cell, see `shared_feature`.
+enum OnCell {
+    /// Along the edge of the cell with this index in `EDGES`.
+    Edge(usize),
+    /// Within the face of the cell with this index in `FACES`, away from its edges.
+    Face(usize),
+}
+
+/// Locates the edge from `shared[axis].0` to `shared[axis].1` on `octant`, the other coordinates
+/// of `shared` are where the edge lies. `None` if the octant does not contain the whole edge
+/// on its boundary.
+fn locate_edge(octant: &Octant, axis: usize, shared: [(f32, f32); 3]) -> Option<OnCell> {
+    let axes = [octant.x_axis, octant.y_axis, octant.z_axis];
+    let (lower, upper) = shared[axis];
+    if lower < axes[axis].lower || upper > axes[axis].upper {
+        return None;
+    }
+
+    // Corner bits selecting the upper x, the lower y and the upper z side of the cell
+    let sides = [(4, true), (2, false), (1, true)];
+    let mut corner = 0;
+    let mut within_face = None;
+    for other in (0..3).filter(|&other| other != axis) {
+        let (at, _) = shared[other];
+        let (bit, bit_is_upper) = sides[other];
+        if at == axes[other].upper || at == axes[other].lower {
+            if (at == axes[other].upper) == bit_is_upper {
+                corner |= bit;
+            }
+        } else if axes[other].contains(at) {
+            within_face = Some(other);
+        } else {
+            return None;
+        }
+    }
+
+    match within_face {
+        None => {
+            let along = EDGES[axis * 4..axis * 4 + 4].iter().position(|&(c0, _)| c0 == corner)?;
+            Some(OnCell::Edge(axis * 4 + along))
+        }
+        Some(within) => {
+            // The edge lies on the face across the remaining axis
+            let across = 3 - axis - within;
+            let bit = sides[across].0;
+            FACES.iter().position(|&face| face == (bit, corner & bit)).map(OnCell::Face)
+        }
+    }
+}
+
+/// The feature of the sheet of surface crossing the shared edge of `make_face` in `octant`.
+///
+/// The edge is either part of an edge of the cell, which then has to have a sign change, or it
+/// lies within a face of a larger cell, whose crossed edges on that face then all have to belong
+/// to a single sheet. Otherwise there is no sheet to connect to.
+fn shared_feature(octant: &Octant, axis: usize, shared: [(f32, f32); 3]) -> Option<&Feature> {
+    match locate_edge(octant, axis, shared)? {
+        OnCell::Edge(edge) => {
+            let (c0, c1) = EDGES[edge];
+            let inside = octant.inside();
+            if (inside >> c0 ^ inside >> c1) & 1 == 0 {
+                return None;
+            }
+            octant.feature_for_edge(edge)
+        }
+        OnCell::Face(face) => {
+            let (bit, value) = FACES[face];
+            let on_face: u16 = (0..12)
+                .filter(|&e| EDGES[e].0 & bit == value && EDGES[e].1 & bit == value)
+                .fold(0, |edges, e| edges | 1 << e);
+            let mut sheets = octant.features.iter().filter(|f| f.edges & on_face != 0);
+            match (sheets.next(), sheets.next()) {
+                (Some(feature), None) => Some(feature),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Creates a face of the polygon around the edge shared by the four leaf cells.
+///
+/// Each cell contributes the feature of the sheet crossing that edge, so cells with several
+/// sheets of surface are connected to the right one. No face is made if one of the cells has no
+/// such sheet, connecting it to another sheet would join separate parts of the surface.
+fn make_face(tree: &Octree, cells: [OctantIdx; 4], placement: Placement) -> Option<Face> {
+    let octants = cells.map(|c| tree.get_octant(c));
+
+    // The shared edge is where all four cells meet, it spans the length of the smallest cell
+    let bounds = |axis: fn(&Octant) -> OctAxis| {
+        let lower = octants.iter().map(|o| axis(o).lower).fold(f32::MIN, f32::max);
+        let upper = octants.iter().map(|o| axis(o).upper).fold(f32::MAX, f32::min);
+        (lower, upper)
+    };
+    let shared = [bounds(|o| o.x_axis), bounds(|o| o.y_axis), bounds(|o| o.z_axis)];
+    let axis = shared.iter().position(|(lower, upper)| upper > lower)?;
+    let at = shared.map(|(lower, upper)| (lower + upper) / 2.0);
+
+    // Only edges with a sign change produce a face. The shortest cell along the edge has it as
+    // one of its own edges.
+    let extent = |o: &Octant| [o.x_axis, o.y_axis, o.z_axis][axis].length();
+    let smallest = octants.iter().min_by(|a, b| extent(a).partial_cmp(&extent(b)).unwrap())?;
+    let edge = match locate_edge(smallest, axis, shared)? {
+        OnCell::Edge(edge) => edge,
+        OnCell::Face(_) => return None,
+    };
+    shared_feature(smallest, axis, shared)?;
+
     // Cells can have duplicated
     let mut dedup_cells = vec![];
-    for x in &cells {
-        if dedup_cells.contains(x) {
-            continue;
-        } else {
-            dedup_cells.push(*x);
+    for (idx, octant) in cells.iter().zip(&octants) {
+        if !dedup_cells.iter().any(|(i, _)| i == idx) {
+            dedup_cells.push((*idx, octant));
         }
     }
-    let dedup_feature_cells: Vec<Point> =
-        dedup_cells.iter().filter_map(|c| tree.get_octant(*c).feature).collect();
+    let dedup_feature_cells: Vec<Point> = dedup_cells
+        .iter()
+        .map(|(_, o)| shared_feature(o, axis, shared).map(placement))
+        .collect::<Option<_>>()?;
 
     // If there aren't three points, a face cannot be constructed
     if dedup_feature_cells.len() < 3 {
         return None;
     }
 
+    // Orient the face so it winds counter-clockwise around the edge when looking against the
+    // direction the shape increases in, which is the direction of the gradient along the edge.
+    // The winding comes from the order of the cells around the edge rather than the feature
+    // points, so faces folded over by sharp features stay consistent with their neighbours.
+    let (c0, _) = EDGES[edge];
+    let increasing = smallest.inside() & (1 << c0) != 0;
+    let ascending = if axis == 1 { !increasing } else { increasing };
+    let loop_order: &[usize] = if dedup_cells.len() == 4 { &[0, 1, 3, 2] } else { &[0, 1, 2] };
+    let winding: f32 = loop_order
+        .iter()
+        .zip(loop_order.iter().cycle().skip(1))
+        .map(|(&i, &j)| {
+            let (a, b) = (cell_center(dedup_cells[i].1), cell_center(dedup_cells[j].1));
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            (a[u] - at[u]) * (b[v] - at[v]) - (a[v] - at[v]) * (b[u] - at[u])
+        })
+        .sum();
+    let flip = (winding > 0.0) != ascending;
+
+    let p = &dedup_feature_cells;
     if dedup_cells.len() == 4 && dedup_feature_cells.len() == 4 {
-        Some(Face::Plane {
-            ul: dedup_feature_cells[0],
-            ur: dedup_feature_cells[1],
-            ll: dedup_feature_cells[2],
-            lr: dedup_feature_cells[3],
+        Some(match flip {
+            false => Face::Plane { ul: p[0], ur: p[1], ll: p[2], lr: p[3] },
+            true => Face::Plane { ul: p[0], ur: p[2], ll: p[1], lr: p[3] },
         })
     } else if dedup_cells.len() == 3 && dedup_feature_cells.len() == 3 {
-        Some(Face::Triangle {
-            ul: dedup_feature_cells[0],
-            lr: dedup_feature_cells[1],
-            ll: dedup_feature_cells[2],
+        Some(match flip {
+            false => Face::Triangle { ul: p[0], lr: p[1], ll: p[2] },
+            true => Face::Triangle { ul: p[0], lr: p[2], ll: p[1] },
         })
     } else {
         None
     }
 }
+
+fn cell_center(octant: &Octant) -> [f32; 3] {
+    let center = |axis: OctAxis| (axis.lower + axis.upper) / 2.0;
+    [center(octant.x_axis), center(octant.y_axis), center(octant.z_axis)]
+}
@@ -1,12 +1,11 @@
 use {
     crate::{
-        octree::{Octant, Octree},
+        progress::{Progress, Stage},
         render_state::RenderState,
-        types::Point,
+        worker::{Job, Meshed, Worker},
     },
     futures::executor,
     log::{error, info, warn},
-    std::sync::mpsc::Receiver,
     winit::{
         event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
         event_loop::{ControlFlow, EventLoop},
@@ -14,17 +13,32 @@ use {
     },
 };
 
+/// Events sent to the event loop from other threads.
+#[derive(Debug)]
+pub enum UserEvent {
+    /// The worker finished a mesh, see `worker::spawn`.
+    Meshed(Meshed),
+    /// The model was loaded, clearing any error shown.
+    Loaded,
+    /// The model could not be loaded, the previous one stays until it is fixed.
+    LoadFailed(String),
+}
+
 pub fn start(
     window: Window,
-    event_loop: EventLoop<()>,
-    ast_reciever: Receiver<crate::shape::CsgFunc>,
+    event_loop: EventLoop<UserEvent>,
+    worker: Worker,
     resolution: f32,
-    bound: f32,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut render_state = executor::block_on(RenderState::new(&window));
     let mut last_render_time = std::time::Instant::now();
 
+    // Meshing happens on the worker (see `worker::spawn`), the previous mesh stays on screen
+    // until it is done
     let mut resolution = resolution;
+    let mut status = None;
+    let mut load_error: Option<String> = None;
+    let mut title_changed = false;
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -48,6 +62,21 @@ pub fn start(
             Event::MainEventsCleared => {
                 // Emitted when all of the event loop’s input events have been processed and redraw
                 // processing is about to begin. Do stuff like update state, calculation, etc... here
+                let progress = worker.progress();
+                let new_status = match progress.stage() {
+                    Stage::Idle => None,
+                    _ => Some(status_line(progress)),
+                };
+                if new_status != status {
+                    let completion = new_status.as_ref().map(|_| progress.completion());
+                    render_state.set_progress_overlay(completion);
+                    status = new_status;
+                    title_changed = true;
+                }
+                if title_changed {
+                    window.set_title(&title(status.as_deref(), load_error.as_deref()));
+                    title_changed = false;
+                }
                 window.request_redraw();
             }
             Event::DeviceEvent { ref event, device_id: _ } => {
@@ -55,8 +84,10 @@ pub fn start(
             }
             Event::WindowEvent { ref event, window_id } if window_id == window.id() => {
                 match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    // Escape only ever cancels meshing, a press meant to cancel a render that just
+                    // finished must not close the window
+                    WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
                                 state: ElementState::Pressed,
@@ -64,6 +95,15 @@ pub fn start(
                                 ..
                             },
                         ..
+                    } => worker.cancel(),
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Q),
+                                ..
+                            },
+                        ..
                     } => *control_flow = ControlFlow::Exit,
                     WindowEvent::KeyboardInput {
                         input:
@@ -76,7 +116,7 @@ pub fn start(
                     } => {
                         resolution += 0.1;
                         info!("Resolution: {}", resolution);
-                        render_octree(&mut render_state, resolution, bound);
+                        let _ = worker.send(Job::Resolution(resolution));
                     }
                     WindowEvent::KeyboardInput {
                         input:
@@ -89,7 +129,7 @@ pub fn start(
                     } => {
                         resolution -= 0.1;
                         info!("Resolution: {}", resolution);
-                        render_octree(&mut render_state, resolution, bound);
+                        let _ = worker.send(Job::Resolution(resolution));
                     }
                     WindowEvent::KeyboardInput { .. }
                     | WindowEvent::MouseWheel { .. }
@@ -112,10 +152,20 @@ pub fn start(
                 // do cleanup after rendering here if needed.
             }
             Event::NewEvents(_) | Event::Suspended | Event::Resumed => {}
-            Event::UserEvent(_) => {
-                let ast = ast_reciever.recv().unwrap();
-                render_state.set_csg_func(ast);
-                render_octree(&mut render_state, resolution, bound);
+            Event::UserEvent(UserEvent::Meshed(meshed)) => {
+                render_state.set_faces_model(meshed.triangles);
+                render_state.set_octree_model(&meshed.outlines);
+                render_state.set_points_model(meshed.points);
+            }
+            Event::UserEvent(UserEvent::Loaded) => {
+                render_state.set_error_banner(None);
+                load_error = None;
+                title_changed = true;
+            }
+            Event::UserEvent(UserEvent::LoadFailed(error)) => {
+                render_state.set_error_banner(Some(&error));
+                load_error = Some(error);
+                title_changed = true;
             }
             Event::WindowEvent { .. } => error!("bad window_id"),
             Event::LoopDestroyed => {}
@@ -123,14 +173,24 @@ pub fn start(
     })
 }
 
-fn render_octree(render_state: &mut RenderState, resolution: f32, bound: f32) {
-    if let Some(csg_func) = &render_state.csg_func {
-        let mut octree = Octree::new(-bound, bound);
-        octree.render_shape(resolution, csg_func);
-        render_state.set_faces_model(octree.extract_faces());
-        let octants: Vec<Octant> = octree.into_iter().collect();
-        render_state.set_octree_model(octants.clone());
-        let points: Vec<Point> = octants.into_iter().filter_map(|o| o.feature).collect();
-        render_state.set_points_model(points);
+/// Describes what the worker is busy with.
+fn status_line(progress: &Progress) -> String {
+    let stage = match progress.stage() {
+        Stage::Extracting => "extracting faces",
+        _ => "rendering octree",
+    };
+    let percent = (progress.completion() * 100.0) as u32;
+    let visited = progress.visited();
+    format!("{} {}%, {} octants (Esc to cancel)", stage, percent, visited)
+}
+
+/// Title of the window, telling what the worker is busy with and why the model failed to load.
+/// The error comes first, so it stays visible however long the title gets.
+fn title(status: Option<&str>, error: Option<&str>) -> String {
+    match (status, error) {
+        (None, None) => "conjure".to_string(),
+        (Some(status), None) => format!("conjure - {}", status),
+        (None, Some(error)) => format!("conjure - error: {}", error),
+        (Some(status), Some(error)) => format!("conjure - error: {} - {}", error, status),
     }
 }
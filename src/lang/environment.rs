@@ -1,16 +1,51 @@
 use {
-    super::{error::Error, types::Ty},
-    std::{cell::RefCell, collections::HashMap, rc::Rc},
+    super::{error::Error, span::SpanTree, types::Ty},
+    crate::shape::CsgFunc,
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        path::{Path, PathBuf},
+        rc::Rc,
+        sync::Arc,
+    },
 };
 
 #[derive(Clone)]
 pub struct Env {
     // TODO Rc/Refcell is a bit lazy, consider doing something
     // more performant
-    lookup: RefCell<HashMap<String, Ty>>,
+    // Shared with clones, so `def` reaches the root through the parents of a scope
+    lookup: Rc<RefCell<HashMap<String, Ty>>>,
     parent: Option<Rc<Env>>,
+    // Shared by every scope
+    shared: Rc<RefCell<Shared>>,
 }
 
+/// State of an evaluation as a whole
+#[derive(Default)]
+struct Shared {
+    /// Every file read so far, in order
+    read: Vec<PathBuf>,
+    /// Files being evaluated, the innermost last
+    open: Vec<PathBuf>,
+    /// Shape picked as the model with `render`
+    rendered: Option<Arc<CsgFunc>>,
+    /// Calls of functions defined in the language in progress
+    depth: usize,
+    /// Forms being evaluated, each inside the one before
+    nesting: usize,
+    /// Whether `include` is refused, see `forbid_includes`
+    no_includes: bool,
+}
+
+/// Calls of functions defined in the language that may be in progress, deeper is an error rather
+/// than overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+/// Forms that may be read or evaluated inside one another, deeper is an error rather than
+/// overflowing the stack
+pub(super) const MAX_NESTING: usize = 256;
+
 impl Env {
     /// Checks if a symbol is present in the current Environment
     pub fn lookup_sym(&self, key: String) -> Result<Ty, Error> {
@@ -28,28 +63,178 @@ impl Env {
         self.lookup.borrow_mut().insert(key, ty);
     }
 
-    /// Creates a child environment with it's own scope
-    pub fn new_env(&self, ty: Ty) -> Result<Rc<Env>, Error> {
-        let lookup = HashMap::new();
-        let env =
-            Rc::new(Env { lookup: RefCell::new(lookup), parent: Some(Rc::new(self.clone())) });
+    /// Registers a symbol in the outermost Environment, visible from every scope
+    pub fn register_root_sym(&self, key: String, ty: Ty) {
+        match &self.parent {
+            Some(p) => p.register_root_sym(key, ty),
+            None => self.register_sym(key, ty),
+        }
+    }
+
+    /// Creates an empty child environment with it's own scope
+    pub fn new_scope(&self) -> Env {
+        Env {
+            lookup: Rc::default(),
+            parent: Some(Rc::new(self.clone())),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Scope seeing what this one sees, with the symbols of every scope but the outermost copied
+    ///
+    /// Functions defined in the language keep this rather than the scope they were defined in,
+    /// which would refer back to them once they are bound in it. Only the outermost Environment
+    /// still does, see `clear`.
+    pub fn snapshot(&self) -> Env {
+        let mut lookup = HashMap::new();
+        let mut scope = self;
+        while let Some(parent) = &scope.parent {
+            for (key, ty) in scope.lookup.borrow().iter() {
+                // Inner scopes shadow outer ones
+                lookup.entry(key.clone()).or_insert_with(|| ty.clone());
+            }
+            scope = parent;
+        }
+        Env {
+            lookup: Rc::new(RefCell::new(lookup)),
+            parent: Some(Rc::new(scope.clone())),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Forgets every symbol registered in the current Environment
+    ///
+    /// Functions defined in the language refer to the outermost Environment, which refers back to
+    /// the ones defined there, so this is needed on it to free them.
+    pub fn clear(&self) {
+        self.lookup.borrow_mut().clear();
+    }
+
+    /// Creates a child environment with it's own scope, binding the pairs in the vector `ty`
+    /// read along with `spans`
+    pub fn new_env(&self, ty: Ty, spans: &SpanTree) -> Result<Rc<Env>, Error> {
+        let env = Rc::new(self.new_scope());
         match ty {
             Ty::Vector(l) => {
-                for pairs in l.chunks(2) {
+                for (idx, pairs) in l.chunks(2).enumerate() {
                     if let [Ty::Symbol(sym), val] = pairs {
-                        env.register_sym(sym.clone(), super::eval::eval(val.clone(), &env)?);
+                        let val_spans = spans.child(idx * 2 + 1);
+                        env.register_sym(
+                            sym.clone(),
+                            super::eval::eval_spanned(val.clone(), val_spans, &env)?,
+                        );
                     } else {
-                        return Err(Error::InvalidLetBinding);
+                        return Err(Error::InvalidLetBinding.at(spans.child(idx * 2).span));
                     }
                 }
                 Ok(env)
             }
-            _ => Err(Error::InvalidLetBinding),
+            _ => Err(Error::InvalidLetBinding.at(spans.span)),
+        }
+    }
+
+    /// Marks `path` as being evaluated until `close_file`, includes are relative to it
+    ///
+    /// Returns an error if `path` is already being evaluated, as it would include itself
+    pub fn open_file(&self, path: &Path) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        // The same file can be reached through different paths
+        let same = |other: &PathBuf| {
+            other == path
+                || matches!((other.canonicalize(), path.canonicalize()), (Ok(a), Ok(b)) if a == b)
+        };
+        if shared.open.iter().any(same) {
+            return Err(Error::IncludeCycle(path.to_owned()));
+        }
+        if !shared.read.iter().any(same) {
+            shared.read.push(path.to_owned());
         }
+        shared.open.push(path.to_owned());
+        Ok(())
+    }
+
+    /// Done evaluating the innermost open file
+    pub fn close_file(&self) {
+        self.shared.borrow_mut().open.pop();
+    }
+
+    /// Path of `file` relative to the file being evaluated
+    pub fn resolve_path(&self, file: &str) -> PathBuf {
+        match self.shared.borrow().open.last().and_then(|open| open.parent()) {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        }
+    }
+
+    /// File being evaluated, if any
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.shared.borrow().open.last().cloned()
+    }
+
+    /// Enters a call of a function defined in the language, until `leave_call`
+    ///
+    /// Returns an error if calls are nested too deep, most likely recursing endlessly
+    pub fn enter_call(&self) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.depth == MAX_DEPTH {
+            return Err(Error::RecursionLimit(MAX_DEPTH));
+        }
+        shared.depth += 1;
+        Ok(())
+    }
+
+    /// Done with the innermost call
+    pub fn leave_call(&self) {
+        self.shared.borrow_mut().depth -= 1;
+    }
+
+    /// Enters the evaluation of a form, until `leave_form`
+    ///
+    /// Returns an error if forms are nested too deep, counting the ones in the functions called
+    pub fn enter_form(&self) -> Result<(), Error> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.nesting == MAX_NESTING {
+            return Err(Error::NestingLimit(MAX_NESTING));
+        }
+        shared.nesting += 1;
+        Ok(())
+    }
+
+    /// Done with the innermost form
+    pub fn leave_form(&self) {
+        self.shared.borrow_mut().nesting -= 1;
+    }
+
+    /// Makes `include` an error from now on, so evaluating never reads files
+    pub fn forbid_includes(&self) {
+        self.shared.borrow_mut().no_includes = true;
+    }
+
+    /// Whether `include` may read files, see `forbid_includes`
+    pub fn includes_allowed(&self) -> bool {
+        !self.shared.borrow().no_includes
+    }
+
+    /// Every file read so far, including the ones that could not be evaluated
+    pub fn files_read(&self) -> Vec<PathBuf> {
+        self.shared.borrow().read.clone()
+    }
+
+    /// Picks `shape` as the model, unless it is rendered from an included file
+    pub fn render(&self, shape: Arc<CsgFunc>) {
+        let mut shared = self.shared.borrow_mut();
+        if shared.open.len() <= 1 {
+            shared.rendered = Some(shape);
+        }
+    }
+
+    /// Takes the shape last picked with `render`
+    pub fn take_rendered(&self) -> Option<Arc<CsgFunc>> {
+        self.shared.borrow_mut().rendered.take()
     }
 
     /// New environment with nothing in scope
     pub fn new() -> Env {
-        Env { lookup: RefCell::new(HashMap::new()), parent: None }
+        Env { lookup: Rc::default(), parent: None, shared: Rc::default() }
     }
 }
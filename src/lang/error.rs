@@ -1,4 +1,8 @@
-use {super::types::Ty, thiserror::Error};
+use {
+    super::{span::Span, types::Ty},
+    std::{io, path::PathBuf},
+    thiserror::Error,
+};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -8,6 +12,10 @@ pub enum Error {
     FormEarlyEnd(usize, usize),
     #[error("Unbalanced String")]
     Unbalanced,
+    #[error("Expected a form but the source ended")]
+    MissingForm,
+    #[error("'{0}' closes nothing")]
+    UnexpectedClose(String),
     #[error("unknown eval error: {0}")]
     UnknownEval(Ty),
     #[error("Symbol '{0}' has not been defined")]
@@ -18,4 +26,66 @@ pub enum Error {
     UnknownTypeCheck,
     #[error("Let Binding Error")]
     InvalidLetBinding,
+    #[error("Def expects a name and a value")]
+    InvalidDef,
+    #[error("Fn expects a vector of parameter names and a body")]
+    InvalidFn,
+    #[error("Defn expects a name, a vector of parameter names and a body")]
+    InvalidDefn,
+    #[error("If expects a test, a branch and an optional other branch")]
+    InvalidIf,
+    #[error("When expects a test and a body")]
+    InvalidWhen,
+    #[error("Cond expects pairs of tests and branches")]
+    InvalidCond,
+    #[error("Expected {0} argument{} but got {1}", if *.0 == 1 { "" } else { "s" })]
+    Arity(usize, usize),
+    #[error("Expected at least {0} argument{} but got {1}", if *.0 == 1 { "" } else { "s" })]
+    MinArity(usize, usize),
+    #[error("Functions are nested more than {0} deep")]
+    RecursionLimit(usize),
+    #[error("Forms are nested more than {0} deep")]
+    NestingLimit(usize),
+    #[error("Render expects a single shape")]
+    InvalidRender,
+    #[error("Include expects the name of a file")]
+    InvalidInclude,
+    #[error("Include is not allowed here")]
+    IncludeForbidden,
+    #[error("{} includes itself", .0.display())]
+    IncludeCycle(PathBuf),
+    #[error("{}: {1}", .0.display())]
+    Read(PathBuf, io::Error),
+    /// Error found in the file at `path`, which reads `text`
+    #[error("{}: {error}", .path.display())]
+    InFile { path: PathBuf, text: String, error: Box<Error> },
+    /// Error found in the source at the span
+    #[error("{1}")]
+    At(Span, Box<Error>),
+}
+
+impl Error {
+    /// Locates the error at `span`, unless it was already located more precisely
+    pub fn at(self, span: Span) -> Error {
+        match self {
+            Error::At(..) => self,
+            error => Error::At(span, Box::new(error)),
+        }
+    }
+
+    /// Where in the source the error was found
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::At(span, _) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The error itself, without where it was found
+    pub fn without_span(self) -> Error {
+        match self {
+            Error::At(_, error) => *error,
+            error => error,
+        }
+    }
 }
@@ -1,44 +1,309 @@
-use super::{environment::Env, error::Error, types::Ty};
+use {
+    super::reader::Reader,
+    super::{
+        environment::Env,
+        error::Error,
+        span::SpanTree,
+        types::{Closure, Ty},
+    },
+    std::{path::Path, rc::Rc},
+};
 
-fn eval_ast(ast: Ty, env: &Env) -> Result<Ty, Error> {
+fn eval_ast(ast: Ty, spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
     match ast {
-        Ty::Symbol(s) => env.lookup_sym(s),
-        Ty::List(l) => Ok(Ty::List(l.into_iter().map(|elem| eval(elem, env).unwrap()).collect())),
+        Ty::Symbol(s) => env.lookup_sym(s).map_err(|e| e.at(spans.span)),
+        Ty::List(l) => Ok(Ty::List(
+            l.into_iter()
+                .enumerate()
+                .map(|(idx, elem)| eval_spanned(elem, spans.child(idx), env))
+                .collect::<Result<_, _>>()?,
+        )),
+        Ty::Vector(v) => Ok(Ty::Vector(
+            v.into_iter()
+                .enumerate()
+                .map(|(idx, elem)| eval_spanned(elem, spans.child(idx), env))
+                .collect::<Result<_, _>>()?,
+        )),
         _ => Ok(ast),
     }
 }
 
+/// Evaluates `ast`, errors carry no location
 pub fn eval(ast: Ty, env: &Env) -> Result<Ty, Error> {
+    eval_spanned(ast, &SpanTree::default(), env).map_err(Error::without_span)
+}
+
+/// Evaluates `ast` read along with `spans` (see `Reader::read_spanned`), errors are located at
+/// the innermost form they were found in
+pub fn eval_spanned(ast: Ty, spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    env.enter_form().map_err(|e| e.at(spans.span))?;
+    let result = eval_form(ast, spans, env);
+    env.leave_form();
+    result
+}
+
+/// Evaluates `ast` once `eval_spanned` made sure it isn't nested too deep
+fn eval_form(ast: Ty, spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
     match ast {
         Ty::List(ref list) => {
-            // Evaluate special forms
-            if let Ty::Symbol(s) = &list[0] {
-                match s.as_str() {
-                    "let" => {
-                        return match &list[1] {
-                            Ty::Vector(_) => {
-                                let new_env = env.new_env(list[1].clone())?;
-                                eval(list[2].clone(), &new_env)
-                            }
-                            _ => Err(Error::InvalidLetBinding),
-                        }
-                    }
-                    _ => {}
-                };
+            if let Some(Ty::Symbol(s)) = list.first() {
+                if let Some(special_form) = special_form(s) {
+                    return special_form(&list[1..], spans, env);
+                }
             }
 
-            let evaled_ast = eval_ast(ast, env)?;
+            let evaled_ast = eval_ast(ast, spans, env)?;
             match evaled_ast {
                 Ty::List(ref l) => {
                     // apply the function `f` on the arguments in the list
-                    if let Ty::Function(f) = &l[0] {
-                        return f(&l[1..]);
+                    match &l[..] {
+                        [Ty::Function(f), args @ ..] => f(args).map_err(|e| e.at(spans.span)),
+                        [Ty::Closure(c), args @ ..] => {
+                            apply(c, args, env).map_err(|e| e.at(spans.span))
+                        }
+                        _ => Ok(evaled_ast),
                     }
-                    Ok(evaled_ast)
                 }
-                _ => Err(Error::UnknownEval(evaled_ast)),
+                _ => Err(Error::UnknownEval(evaled_ast).at(spans.span)),
+            }
+        }
+        e => eval_ast(e, spans, env),
+    }
+}
+
+/// Evaluates the arguments of a special form, read along with the spans of the whole form
+type SpecialForm = fn(&[Ty], &SpanTree, &Env) -> Result<Ty, Error>;
+
+/// The special form named `name`, if it is one
+///
+/// Each has a function of its own, so evaluating nested forms only takes the stack the ones in
+/// use need.
+fn special_form(name: &str) -> Option<SpecialForm> {
+    Some(match name {
+        "let" => eval_let,
+        "if" => eval_if,
+        "when" => eval_when,
+        "cond" => eval_cond,
+        "and" => eval_and,
+        "or" => eval_or,
+        "not" => eval_not,
+        "def" => eval_def,
+        "fn" => eval_fn,
+        "defn" => eval_defn,
+        "render" => eval_render,
+        "include" => eval_include,
+        _ => return None,
+    })
+}
+
+fn eval_let(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [bindings @ Ty::Vector(_), body] => {
+            let new_env = env.new_env(bindings.clone(), spans.child(1))?;
+            eval_spanned(body.clone(), spans.child(2), &new_env)
+        }
+        _ => Err(Error::InvalidLetBinding.at(spans.span)),
+    }
+}
+
+fn eval_if(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [test, then, otherwise @ ..] if otherwise.len() <= 1 => {
+            if eval_spanned(test.clone(), spans.child(1), env)?.is_truthy() {
+                eval_spanned(then.clone(), spans.child(2), env)
+            } else if let [otherwise] = otherwise {
+                eval_spanned(otherwise.clone(), spans.child(3), env)
+            } else {
+                Ok(Ty::List(vec![]))
+            }
+        }
+        _ => Err(Error::InvalidIf.at(spans.span)),
+    }
+}
+
+fn eval_when(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [test, body] => {
+            if eval_spanned(test.clone(), spans.child(1), env)?.is_truthy() {
+                eval_spanned(body.clone(), spans.child(2), env)
+            } else {
+                Ok(Ty::List(vec![]))
+            }
+        }
+        _ => Err(Error::InvalidWhen.at(spans.span)),
+    }
+}
+
+fn eval_cond(clauses: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    if !clauses.len().is_multiple_of(2) {
+        return Err(Error::InvalidCond.at(spans.span));
+    }
+    // The branch of the first test that holds
+    for (idx, clause) in clauses.chunks(2).enumerate() {
+        let test = spans.child(1 + idx * 2);
+        if eval_spanned(clause[0].clone(), test, env)?.is_truthy() {
+            return eval_spanned(clause[1].clone(), spans.child(2 + idx * 2), env);
+        }
+    }
+    Ok(Ty::List(vec![]))
+}
+
+fn eval_and(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    // The first value that is false, or the last
+    let mut last = Ty::True;
+    for (idx, arg) in args.iter().enumerate() {
+        last = eval_spanned(arg.clone(), spans.child(1 + idx), env)?;
+        if !last.is_truthy() {
+            break;
+        }
+    }
+    Ok(last)
+}
+
+fn eval_or(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    // The first value that is true, or the last
+    let mut last = Ty::False;
+    for (idx, arg) in args.iter().enumerate() {
+        last = eval_spanned(arg.clone(), spans.child(1 + idx), env)?;
+        if last.is_truthy() {
+            break;
+        }
+    }
+    Ok(last)
+}
+
+fn eval_not(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [arg] => Ok((!eval_spanned(arg.clone(), spans.child(1), env)?.is_truthy()).into()),
+        args => Err(Error::Arity(1, args.len()).at(spans.span)),
+    }
+}
+
+fn eval_def(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [Ty::Symbol(name), value] => {
+            let value = eval_spanned(value.clone(), spans.child(2), env)?;
+            env.register_root_sym(name.clone(), value);
+            Ok(Ty::Symbol(name.clone()))
+        }
+        _ => Err(Error::InvalidDef.at(spans.span)),
+    }
+}
+
+fn eval_fn(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [Ty::Vector(params), body] => closure(params, body, spans.child(2), env)
+            .ok_or_else(|| Error::InvalidFn.at(spans.span)),
+        _ => Err(Error::InvalidFn.at(spans.span)),
+    }
+}
+
+fn eval_defn(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [Ty::Symbol(name), Ty::Vector(params), body] => {
+            let closure = closure(params, body, spans.child(3), env)
+                .ok_or_else(|| Error::InvalidDefn.at(spans.span))?;
+            env.register_root_sym(name.clone(), closure);
+            Ok(Ty::Symbol(name.clone()))
+        }
+        _ => Err(Error::InvalidDefn.at(spans.span)),
+    }
+}
+
+fn eval_render(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [shape] => match eval_spanned(shape.clone(), spans.child(1), env)? {
+            Ty::CsgFunc(shape) => {
+                env.render(shape.clone());
+                Ok(Ty::CsgFunc(shape))
+            }
+            ty => Err(Error::InvalidType(ty).at(spans.child(1).span)),
+        },
+        _ => Err(Error::InvalidRender.at(spans.span)),
+    }
+}
+
+fn eval_include(args: &[Ty], spans: &SpanTree, env: &Env) -> Result<Ty, Error> {
+    match args {
+        [Ty::Str(_)] if !env.includes_allowed() => Err(Error::IncludeForbidden.at(spans.span)),
+        [Ty::Str(file)] => {
+            let path = env.resolve_path(file);
+            env.open_file(&path).map_err(|e| e.at(spans.child(1).span))?;
+            let result = eval_file(&path, env);
+            env.close_file();
+            result
+        }
+        _ => Err(Error::InvalidInclude.at(spans.span)),
+    }
+}
+
+/// Reads and evaluates the forms in the file at `path` in order, in `env`, which should have the
+/// file open (see `Env::open_file`) so includes are relative to it
+///
+/// Returns the value of the last form that is a shape, or of the last form if none are. A file
+/// without forms evaluates to an empty list. Errors are `Error::Read` or `Error::InFile`, for the
+/// innermost file they were found in.
+pub fn eval_file(path: &Path, env: &Env) -> Result<Ty, Error> {
+    let text =
+        std::fs::read_to_string(path).map_err(|error| Error::Read(path.to_owned(), error))?;
+    let in_file = |error: Error| {
+        let span = error.span().unwrap_or_default();
+        match error.without_span() {
+            // Already located in an included file
+            error @ (Error::Read(..) | Error::InFile { .. }) => error,
+            error => {
+                let error = Box::new(error.at(span));
+                Error::InFile { path: path.to_owned(), text: text.clone(), error }
             }
         }
-        e => eval_ast(e, env),
+    };
+    let forms = Reader::read_all_spanned(&text).map_err(in_file)?;
+
+    let (mut last, mut last_shape) = (Ty::List(vec![]), None);
+    for (ast, spans) in forms {
+        last = eval_spanned(ast, &spans, env).map_err(in_file)?;
+        if let Ty::CsgFunc(_) = last {
+            last_shape = Some(last.clone());
+        }
+    }
+    Ok(last_shape.unwrap_or(last))
+}
+
+/// Function taking `params` and evaluating `body` read along with `spans`, `None` if the
+/// parameters aren't all names
+fn closure(params: &[Ty], body: &Ty, spans: &SpanTree, env: &Env) -> Option<Ty> {
+    let params = params
+        .iter()
+        .map(|param| match param {
+            Ty::Symbol(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(Ty::Closure(Rc::new(Closure {
+        params,
+        body: body.clone(),
+        spans: spans.clone(),
+        env: env.snapshot(),
+        file: env.current_file(),
+    })))
+}
+
+/// Calls `closure` on `args`, from `env`
+fn apply(closure: &Closure, args: &[Ty], env: &Env) -> Result<Ty, Error> {
+    if args.len() != closure.params.len() {
+        return Err(Error::Arity(closure.params.len(), args.len()));
+    }
+    let scope = closure.env.new_scope();
+    for (param, arg) in closure.params.iter().zip(args) {
+        scope.register_sym(param.clone(), arg.clone());
+    }
+    env.enter_call()?;
+    let result = eval_spanned(closure.body.clone(), &closure.spans, &scope);
+    env.leave_call();
+    // The spans of the body are in the file it was defined in, elsewhere the call is pointed at
+    if closure.file == env.current_file() {
+        result
+    } else {
+        result.map_err(Error::without_span)
     }
 }
@@ -3,10 +3,13 @@ mod error;
 mod eval;
 mod namespace;
 mod reader;
+mod span;
 mod types;
 
 pub use environment::Env;
-pub use eval::eval;
+pub use error::Error;
+pub use eval::{eval, eval_file, eval_spanned};
 pub use namespace::Namespace;
 pub use reader::Reader;
-pub use types::Ty;
+pub use span::{Span, SpanTree};
+pub use types::{Closure, Ty};
@@ -1,6 +1,9 @@
 use {
     super::{error::Error, types::Ty},
-    crate::shape::CsgFunc,
+    crate::{
+        shape::{CsgFunc, ShapeNode},
+        types::{Aabb, Point},
+    },
     std::{
         collections::{hash_map::IntoIter, HashMap},
         sync::Arc,
@@ -13,25 +16,50 @@ impl Namespace {
     pub fn new() -> Namespace {
         let mut ns = Namespace(HashMap::new());
 
-        // addition
-        ns.add_function("+", |list| {
-            let mut accum = 0.0;
-            for num in list {
-                match num {
-                    Ty::Number(n) => {
-                        accum += n;
-                    }
-                    t => return Err(Error::InvalidType(t.clone())),
-                }
-            }
-            Ok(Ty::Number(accum))
+        // arithmetic
+        ns.add_function("+", |list| Ok(Ty::Number(numbers(list)?.iter().sum())));
+        ns.add_function("*", |list| Ok(Ty::Number(numbers(list)?.iter().product())));
+        // subtraction, or negation of a single number
+        ns.add_function("-", |list| match numbers(list)?.as_slice() {
+            [] => Err(Error::MinArity(1, 0)),
+            [n] => Ok(Ty::Number(-n)),
+            [first, rest @ ..] => Ok(Ty::Number(rest.iter().fold(*first, |a, b| a - b))),
+        });
+        // division, or the reciprocal of a single number
+        ns.add_function("/", |list| match numbers(list)?.as_slice() {
+            [] => Err(Error::MinArity(1, 0)),
+            [n] => Ok(Ty::Number(1.0 / n)),
+            [first, rest @ ..] => Ok(Ty::Number(rest.iter().fold(*first, |a, b| a / b))),
         });
+        // remainder with the sign of the divisor
+        ns.add_function("mod", |list| binary(list, |a, b| (a % b + b) % b));
+        ns.add_function("min", |list| fold(list, f32::min));
+        ns.add_function("max", |list| fold(list, f32::max));
+        ns.add_function("abs", |list| unary(list, f32::abs));
+        ns.add_function("sqrt", |list| unary(list, f32::sqrt));
+        ns.add_function("pow", |list| binary(list, f32::powf));
+        ns.add_function("floor", |list| unary(list, f32::floor));
+        ns.add_function("ceil", |list| unary(list, f32::ceil));
+        ns.add_function("round", |list| unary(list, f32::round));
 
-        // subtraction
-        ns.add_function("-", |list| match list {
-            [Ty::Number(n), Ty::Number(m)] => Ok(Ty::Number(n - m)),
-            _ => Err(Error::UnknownTypeCheck),
+        // trigonometry, in radians
+        ns.0.insert("pi".to_string(), Ty::Number(std::f32::consts::PI));
+        ns.add_function("deg->rad", |list| unary(list, f32::to_radians));
+        ns.add_function("sin", |list| unary(list, f32::sin));
+        ns.add_function("cos", |list| unary(list, f32::cos));
+        ns.add_function("tan", |list| unary(list, f32::tan));
+        // angle of the point (x, y), called as (atan2 y x)
+        ns.add_function("atan2", |list| binary(list, f32::atan2));
+
+        // comparisons, holding for every pair of neighbouring arguments
+        ns.add_function("=", |list| match list {
+            [first, rest @ ..] => Ok(rest.iter().all(|ty| ty == first).into()),
+            [] => Err(Error::MinArity(1, 0)),
         });
+        ns.add_function("<", |list| compare(list, |a, b| a < b));
+        ns.add_function(">", |list| compare(list, |a, b| a > b));
+        ns.add_function("<=", |list| compare(list, |a, b| a <= b));
+        ns.add_function(">=", |list| compare(list, |a, b| a >= b));
 
         // CSG
 
@@ -40,9 +68,20 @@ impl Namespace {
             [Ty::CsgFunc(fn1), Ty::CsgFunc(fn2)] => {
                 let fn1 = fn1.clone();
                 let fn2 = fn2.clone();
-                let func = CsgFunc::new(Box::new(move |x, y, z| {
+                let bounds = match (fn1.bounds(), fn2.bounds()) {
+                    (Some(b1), Some(b2)) => Some(b1.union(&b2)),
+                    _ => None,
+                };
+                let mut func = CsgFunc::new(Box::new(move |x, y, z| {
                     f32::min(fn1.call(x, y, z), fn2.call(x, y, z))
                 }));
+                func.set_bounds(bounds);
+                func.set_node(ShapeNode {
+                    op: "union",
+                    params: vec![],
+                    children: list_shapes(list),
+                    children_in_place: true,
+                });
                 Ok(Ty::CsgFunc(Arc::new(func)))
             }
             _ => Err(Error::UnknownTypeCheck),
@@ -53,9 +92,20 @@ impl Namespace {
             [Ty::CsgFunc(fn1), Ty::CsgFunc(fn2)] => {
                 let fn1 = fn1.clone();
                 let fn2 = fn2.clone();
-                let func = CsgFunc::new(Box::new(move |x, y, z| {
+                let bounds = match (fn1.bounds(), fn2.bounds()) {
+                    (Some(b1), Some(b2)) => Some(b1.intersection(&b2)),
+                    (bounds, None) | (None, bounds) => bounds,
+                };
+                let mut func = CsgFunc::new(Box::new(move |x, y, z| {
                     f32::max(fn1.call(x, y, z), fn2.call(x, y, z))
                 }));
+                func.set_bounds(bounds);
+                func.set_node(ShapeNode {
+                    op: "intersect",
+                    params: vec![],
+                    children: list_shapes(list),
+                    children_in_place: true,
+                });
                 Ok(Ty::CsgFunc(Arc::new(func)))
             }
             _ => Err(Error::UnknownTypeCheck),
@@ -66,9 +116,17 @@ impl Namespace {
             [Ty::Number(factor), Ty::CsgFunc(func)] => {
                 let factor = *factor;
                 let func = func.clone();
-                let scaled_func = CsgFunc::new(Box::new(move |x, y, z| {
+                let bounds = func.bounds().map(|bounds| bounds.scale(factor));
+                let mut scaled_func = CsgFunc::new(Box::new(move |x, y, z| {
                     func.call(x / factor, y / factor, z / factor)
                 }));
+                scaled_func.set_bounds(bounds);
+                scaled_func.set_node(ShapeNode {
+                    op: "scale",
+                    params: vec![factor],
+                    children: list_shapes(list),
+                    children_in_place: false,
+                });
                 Ok(Ty::CsgFunc(Arc::new(scaled_func)))
             }
             _ => Err(Error::UnknownTypeCheck),
@@ -78,11 +136,19 @@ impl Namespace {
         ns.add_function("sphere", |list| match list {
             [Ty::Number(n)] => {
                 let radius = *n;
-                let func = CsgFunc::new(Box::new(move |x, y, z| {
+                let mut func = CsgFunc::new(Box::new(move |x, y, z| {
                     (((0.0 - z) * (0.0 - z)) + ((0.0 - x) * (0.0 - x)) + ((0.0 - y) * (0.0 - y)))
                         .sqrt()
                         - radius
                 }));
+                let r = radius.abs();
+                func.set_bounds(Some(Aabb::new(Point::new(-r, -r, -r), Point::new(r, r, r))));
+                func.set_node(ShapeNode {
+                    op: "sphere",
+                    params: vec![radius],
+                    children: vec![],
+                    children_in_place: true,
+                });
                 Ok(Ty::CsgFunc(Arc::new(func)))
             }
             _ => Err(Error::UnknownTypeCheck),
@@ -103,7 +169,7 @@ impl Namespace {
                     _ => return Err(Error::UnknownTypeCheck),
                 };
 
-                let func = CsgFunc::new(Box::new(move |x, y, z| {
+                let mut func = CsgFunc::new(Box::new(move |x, y, z| {
                     f32::max(
                         z - ur_z,
                         f32::max(
@@ -112,6 +178,14 @@ impl Namespace {
                         ),
                     )
                 }));
+                let (ll, ur) = (Point::new(ll_x, ll_y, ll_z), Point::new(ur_x, ur_y, ur_z));
+                func.set_bounds(Some(Aabb::new(ll, ur)));
+                func.set_node(ShapeNode {
+                    op: "cube",
+                    params: vec![ll_x, ll_y, ll_z, ur_x, ur_y, ur_z],
+                    children: vec![],
+                    children_in_place: true,
+                });
                 Ok(Ty::CsgFunc(Arc::new(func)))
             }
             _ => Err(Error::UnknownTypeCheck),
@@ -125,6 +199,59 @@ impl Namespace {
     }
 }
 
+/// The arguments, which all have to be numbers.
+fn numbers(list: &[Ty]) -> Result<Vec<f32>, Error> {
+    list.iter()
+        .map(|ty| match ty {
+            Ty::Number(n) => Ok(*n),
+            t => Err(Error::InvalidType(t.clone())),
+        })
+        .collect()
+}
+
+/// Applies `f` to the single number in `list`.
+fn unary(list: &[Ty], f: fn(f32) -> f32) -> Result<Ty, Error> {
+    match numbers(list)?.as_slice() {
+        [n] => Ok(Ty::Number(f(*n))),
+        _ => Err(Error::Arity(1, list.len())),
+    }
+}
+
+/// Applies `f` to the two numbers in `list`.
+fn binary(list: &[Ty], f: fn(f32, f32) -> f32) -> Result<Ty, Error> {
+    match numbers(list)?.as_slice() {
+        [a, b] => Ok(Ty::Number(f(*a, *b))),
+        _ => Err(Error::Arity(2, list.len())),
+    }
+}
+
+/// Combines the numbers in `list` with `f`, from the left.
+fn fold(list: &[Ty], f: fn(f32, f32) -> f32) -> Result<Ty, Error> {
+    match numbers(list)?.as_slice() {
+        [first, rest @ ..] => Ok(Ty::Number(rest.iter().fold(*first, |a, b| f(a, *b)))),
+        [] => Err(Error::MinArity(1, 0)),
+    }
+}
+
+/// Whether `holds` for every pair of neighbouring numbers in `list`.
+fn compare(list: &[Ty], holds: fn(f32, f32) -> bool) -> Result<Ty, Error> {
+    if list.is_empty() {
+        return Err(Error::MinArity(1, 0));
+    }
+    let numbers = numbers(list)?;
+    Ok(numbers.windows(2).all(|pair| holds(pair[0], pair[1])).into())
+}
+
+/// The shapes among the arguments of a function, in order.
+fn list_shapes(list: &[Ty]) -> Vec<Arc<CsgFunc>> {
+    list.iter()
+        .filter_map(|arg| match arg {
+            Ty::CsgFunc(func) => Some(func.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
 impl IntoIterator for Namespace {
     type Item = (String, Ty);
     type IntoIter = IntoIter<String, Ty>;
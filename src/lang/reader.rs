@@ -1,6 +1,8 @@
 use {
     super::{
+        environment::MAX_NESTING,
         error::Error,
+        span::{Span, SpanTree},
         types::{KeyTy, Ty},
     },
     regex::Regex,
@@ -9,95 +11,159 @@ use {
 
 pub struct Reader<'a> {
     idx: usize,
-    tokens: Vec<&'a str>,
+    tokens: Vec<Token<'a>>,
     count: usize,
+    /// Spans of the brackets not closed yet, the innermost last
+    open: Vec<Span>,
+    /// Empty span at the end of the source
+    end: Span,
+}
+
+/// Piece of the source along with where it is
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    span: Span,
 }
 
 impl<'a> Reader<'a> {
-    /// Converts a string to an AST
+    /// Converts the first form in a string to an AST
     ///
     /// Returns an error if the AST is not well formed
     pub fn read_str(string: &str) -> Result<Ty, Error> {
-        // strip comment tokens
-        let tokens = tokenize(string).into_iter().filter(|t| !t.starts_with(';')).collect();
-        let mut reader = Reader::new(tokens);
-        reader.read_form()
+        Self::read_spanned(string).map(|(ty, _)| ty).map_err(Error::without_span)
     }
 
-    /// Converts a single form to an AST
-    fn read_form(&mut self) -> Result<Ty, Error> {
-        if self.idx + 1 == self.tokens.len() && self.count != 0 {
-            return Err(Error::FormEarlyEnd(self.tokens.len(), self.count));
+    /// Same as `read_str`, along with the spans of every form in the AST
+    ///
+    /// Errors are located at the token they were found at.
+    pub fn read_spanned(string: &str) -> Result<(Ty, SpanTree), Error> {
+        Reader::new(string).read_form()
+    }
+
+    /// Reads every form in the string, in order, along with their spans
+    pub fn read_all_spanned(string: &str) -> Result<Vec<(Ty, SpanTree)>, Error> {
+        let mut reader = Reader::new(string);
+        let mut forms = vec![];
+        while reader.idx < reader.tokens.len() {
+            forms.push(reader.read_form()?);
         }
-        match self.tokens[self.idx] {
-            "(" => {
-                self.idx += 1;
-                self.count += 1;
-                self.read_list()
-            }
-            "[" => {
-                self.idx += 1;
-                self.count += 1;
-                self.read_list()
+        Ok(forms)
+    }
+
+    /// Converts a single form to an AST
+    fn read_form(&mut self) -> Result<(Ty, SpanTree), Error> {
+        let token = self.token()?;
+        match token.text {
+            ")" | "]" | "}" => Err(Error::UnexpectedClose(token.text.to_string()).at(token.span)),
+            "(" | "[" => {
+                self.open(token)?;
+                self.read_list(token.span)
             }
             "{" => {
-                self.idx += 1;
-                self.count += 1;
-                self.read_hashmap()
+                self.open(token)?;
+                self.read_hashmap(token.span)
             }
             _ => self.read_atom(),
         }
     }
 
     /// Converts a Vector [] or List () into an AST
-    fn read_list(&mut self) -> Result<Ty, Error> {
+    fn read_list(&mut self, start: Span) -> Result<(Ty, SpanTree), Error> {
         let mut accum = vec![];
+        let mut spans = vec![];
         loop {
-            match self.tokens[self.idx] {
+            let token = self.token()?;
+            match token.text {
                 "]" => {
-                    self.idx += 1;
-                    self.count -= 1;
-                    return Ok(Ty::Vector(accum));
+                    self.close();
+                    return Ok((Ty::Vector(accum), SpanTree::new(start.to(token.span), spans)));
                 }
                 ")" => {
-                    self.idx += 1;
-                    self.count -= 1;
-                    return Ok(Ty::List(accum));
+                    self.close();
+                    return Ok((Ty::List(accum), SpanTree::new(start.to(token.span), spans)));
+                }
+                _ => {
+                    let (ty, span) = self.read_form()?;
+                    accum.push(ty);
+                    spans.push(span);
                 }
-                _ => accum.push(self.read_form()?),
             }
         }
     }
 
     /// Converts a Hashmap {} into an AST
-    fn read_hashmap(&mut self) -> Result<Ty, Error> {
+    fn read_hashmap(&mut self, start: Span) -> Result<(Ty, SpanTree), Error> {
         let mut accum = HashMap::new();
+        let mut spans = vec![];
         let mut key: Option<KeyTy> = None;
         loop {
-            match self.tokens[self.idx] {
+            let token = self.token()?;
+            match token.text {
                 "}" => {
-                    self.idx += 1;
-                    self.count -= 1;
-                    return Ok(Ty::HashMap(accum));
+                    self.close();
+                    return Ok((Ty::HashMap(accum), SpanTree::new(start.to(token.span), spans)));
                 }
-                _ => match key {
-                    Some(k) => {
-                        key = None;
-                        accum.insert(k, self.read_form()?);
-                    }
-                    None => {
-                        key = Some(self.read_form()?.try_into()?);
+                _ => {
+                    let (ty, span) = self.read_form()?;
+                    match key {
+                        Some(k) => {
+                            key = None;
+                            accum.insert(k, ty);
+                        }
+                        None => {
+                            key = Some(ty.try_into().map_err(|e: Error| e.at(span.span))?);
+                        }
                     }
-                },
+                    spans.push(span);
+                }
             }
         }
     }
 
+    /// The token being read, the form ended early if there are none left
+    fn token(&self) -> Result<Token<'a>, Error> {
+        match self.tokens.get(self.idx) {
+            Some(token) => Ok(*token),
+            // Point at the bracket that was left open, or the end of the source
+            None => match self.open.last() {
+                Some(open) => Err(Error::FormEarlyEnd(self.tokens.len(), self.count).at(*open)),
+                None => Err(Error::MissingForm.at(self.end)),
+            },
+        }
+    }
+
+    /// Steps into a list, vector or hashmap opened by `token`
+    ///
+    /// Returns an error if it is nested too deep to read without overflowing the stack
+    fn open(&mut self, token: Token) -> Result<(), Error> {
+        if self.count == MAX_NESTING {
+            return Err(Error::NestingLimit(MAX_NESTING).at(token.span));
+        }
+        self.idx += 1;
+        self.count += 1;
+        self.open.push(token.span);
+        Ok(())
+    }
+
+    /// Steps out of the innermost list, vector or hashmap
+    fn close(&mut self) {
+        self.idx += 1;
+        self.count -= 1;
+        self.open.pop();
+    }
+
     /// Makes an atom into an AST
-    fn read_atom(&mut self) -> Result<Ty, Error> {
-        let token = self.tokens[self.idx];
+    fn read_atom(&mut self) -> Result<(Ty, SpanTree), Error> {
+        let token = self.token()?;
         self.idx += 1;
+        let spans = SpanTree::new(token.span, vec![]);
+        let ty = Self::atom(token.text).map_err(|e| e.at(token.span))?;
+        Ok((ty, spans))
+    }
 
+    /// Reads a single token as an atom
+    fn atom(token: &str) -> Result<Ty, Error> {
         if let Ok(i) = token.parse::<f32>() {
             return Ok(Ty::Number(i));
         }
@@ -136,8 +202,11 @@ impl<'a> Reader<'a> {
         })
     }
 
-    fn new(tokens: Vec<&'a str>) -> Reader<'a> {
-        Reader { count: 0, idx: 0, tokens }
+    fn new(string: &'a str) -> Reader<'a> {
+        // strip comment tokens
+        let tokens = tokenize(string).into_iter().filter(|t| !t.text.starts_with(';')).collect();
+        let end = Span::new(string.len(), string.len());
+        Reader { count: 0, idx: 0, tokens, open: vec![], end }
     }
 
     /// Convert the AST ty to a string
@@ -176,6 +245,7 @@ impl<'a> Reader<'a> {
             Ty::Symbol(n) => n.to_string(),
             Ty::Keyword(s) => format!(":{}", s),
             Ty::Function(_) => "<func>".to_string(),
+            Ty::Closure(_) => "<fn>".to_string(),
             Ty::CsgFunc(_) => "<csg>".to_string(),
             Ty::Str(n) => {
                 if pretty {
@@ -200,15 +270,13 @@ impl<'a> Reader<'a> {
     }
 }
 
-/// Split the text into a vector of token strings
-fn tokenize(string: &str) -> Vec<&str> {
+/// Split the text into a vector of tokens, each with its span in the text
+fn tokenize(string: &str) -> Vec<Token<'_>> {
     let re = Regex::new(r#"[\s,]*(~@|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|;.*|[^\s\[\]{}('"`,;)]*)"#)
         .unwrap();
-    re.find_iter(string)
-        .map(|c| {
-            c.as_str()
-                .trim_start_matches(|c| c == ',' || c == ' ' || c == '\n')
-                .trim_end_matches(|c| c == ',' || c == ' ' || c == '\n')
-        })
+    re.captures_iter(string)
+        .filter_map(|c| c.get(1))
+        .filter(|token| !token.as_str().is_empty())
+        .map(|token| Token { text: token.as_str(), span: Span::new(token.start(), token.end()) })
         .collect()
 }
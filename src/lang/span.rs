@@ -0,0 +1,39 @@
+/// Byte range of a token or form in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Span covering both spans and everything in between
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+}
+
+/// Where a form read from the source is, along with its elements
+///
+/// Mirrors the `Ty` that was read: the children of a list or vector are the spans of its
+/// elements, in order. The keys and values of a hashmap are in the order they were written.
+/// Atoms have no children.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanTree {
+    pub span: Span,
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    pub fn new(span: Span, children: Vec<SpanTree>) -> SpanTree {
+        SpanTree { span, children }
+    }
+
+    /// Spans of the `idx`th element, falling back to the whole form for forms built by hand
+    pub fn child(&self, idx: usize) -> &SpanTree {
+        self.children.get(idx).unwrap_or(self)
+    }
+}
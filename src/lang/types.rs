@@ -1,7 +1,7 @@
 use {
-    super::error::Error,
+    super::{environment::Env, error::Error, span::SpanTree},
     crate::shape::CsgFunc,
-    std::{collections::HashMap, fmt, sync::Arc},
+    std::{collections::HashMap, fmt, path::PathBuf, rc::Rc, sync::Arc},
 };
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -22,9 +22,23 @@ pub enum Ty {
     Keyword(String),
     HashMap(HashMap<KeyTy, Ty>),
     Function(fn(&[Ty]) -> Result<Ty, Error>),
+    Closure(Rc<Closure>),
     CsgFunc(Arc<CsgFunc>),
 }
 
+/// Function defined in the language with `fn` or `defn`
+pub struct Closure {
+    pub params: Vec<String>,
+    pub body: Ty,
+    /// Spans of the body, in `file`
+    pub spans: SpanTree,
+    /// Environment the function was defined in, its body can see everything in it, see
+    /// `Env::snapshot`
+    pub env: Env,
+    /// File the function was defined in
+    pub file: Option<PathBuf>,
+}
+
 impl std::fmt::Debug for Ty {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -37,8 +51,49 @@ impl std::fmt::Debug for Ty {
             Ty::False => f.debug_struct("False").finish(),
             Ty::True => f.debug_struct("True").finish(),
             Ty::Function(_) => f.debug_struct("<function>").finish(),
+            Ty::Closure(c) => f.debug_struct("<fn>").field("params", &c.params).finish(),
             Ty::CsgFunc(_) => f.debug_struct("<csg>").finish(),
-            _ => panic!("DEBUG not implemented"),
+            Ty::HashMap(m) => f.debug_map().entries(m.iter()).finish(),
+        }
+    }
+}
+
+impl Ty {
+    /// Whether the value counts as true in a condition, only `false` and the empty list don't
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Ty::False => false,
+            Ty::List(list) => !list.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+impl From<bool> for Ty {
+    fn from(b: bool) -> Ty {
+        if b {
+            Ty::True
+        } else {
+            Ty::False
+        }
+    }
+}
+
+/// Values are equal when they are the same, shapes and functions defined in the language only
+/// to themselves. Builtin functions are never equal.
+impl PartialEq for Ty {
+    fn eq(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Ty::Number(a), Ty::Number(b)) => a == b,
+            (Ty::True, Ty::True) | (Ty::False, Ty::False) => true,
+            (Ty::Str(a), Ty::Str(b))
+            | (Ty::Symbol(a), Ty::Symbol(b))
+            | (Ty::Keyword(a), Ty::Keyword(b)) => a == b,
+            (Ty::List(a), Ty::List(b)) | (Ty::Vector(a), Ty::Vector(b)) => a == b,
+            (Ty::HashMap(a), Ty::HashMap(b)) => a == b,
+            (Ty::Closure(a), Ty::Closure(b)) => Rc::ptr_eq(a, b),
+            (Ty::CsgFunc(a), Ty::CsgFunc(b)) => Arc::ptr_eq(a, b),
+            _ => false,
         }
     }
 }
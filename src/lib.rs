@@ -6,10 +6,20 @@ pub mod camera;
 pub mod dual_contour;
 pub mod event_loop;
 pub mod lang;
+pub mod load;
+pub mod mass;
+pub mod mesh;
+pub mod mesher;
 pub mod model;
 pub mod octree;
+pub mod progress;
+pub mod qef;
+pub mod query;
+pub mod remesh;
 pub mod render_state;
 pub mod shape;
 pub mod texture;
 pub mod types;
 pub mod util;
+pub mod watch;
+pub mod worker;
@@ -0,0 +1,171 @@
+use {
+    crate::{
+        lang::{self, Span, Ty},
+        shape::CsgFunc,
+    },
+    std::{
+        fmt, io,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+    thiserror::Error,
+};
+
+/// Line and column in a source file, both starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// Location of the byte `offset` into `source`.
+    pub fn of(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Why a model could not be loaded, printed along with the file and where in it things went wrong.
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("{}: {1}", .0.display())]
+    Io(PathBuf, io::Error),
+    #[error("{}:{location}: {error}", .path.display())]
+    Lang {
+        path: PathBuf,
+        /// Where the error is in the source.
+        span: Span,
+        location: Location,
+        /// Line of the source the error starts on.
+        line: String,
+        error: Box<lang::Error>,
+    },
+    #[error("{}: does not evaluate to a shape", .0.display())]
+    NotAShape(PathBuf),
+}
+
+impl LoadError {
+    /// Locates `error` found in `source`, read from `path`.
+    fn lang(path: &Path, source: &str, error: lang::Error) -> Self {
+        let span = error.span().unwrap_or_default();
+        let location = Location::of(source, span.start);
+        let before = &source[..span.start.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let line = source[line_start..].lines().next().unwrap_or("").to_string();
+        LoadError::Lang {
+            path: path.to_owned(),
+            span,
+            location,
+            line,
+            error: Box::new(error.without_span()),
+        }
+    }
+
+    /// Where in the file the error was found, if it was in the file at all.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            LoadError::Lang { location, .. } => Some(*location),
+            _ => None,
+        }
+    }
+
+    /// The error the way a compiler shows it, with a caret under the source it was found in.
+    ///
+    /// ```text
+    /// error: Symbol 'spher' has not been defined
+    ///  --> model.cnj:3:4
+    ///   |
+    /// 3 |   (spher 4)
+    ///   |    ^^^^^
+    /// ```
+    pub fn diagnostic(&self) -> String {
+        match self {
+            LoadError::Lang { path, span, location, line, error } => {
+                let gutter = " ".repeat(location.line.to_string().len());
+                // Tabs are kept so the caret lines up however wide they are shown
+                let indent: String = line
+                    .chars()
+                    .take(location.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let start =
+                    line.char_indices().nth(location.column - 1).map_or(line.len(), |(i, _)| i);
+                let end = (start + span.end - span.start).min(line.len());
+                let carets = "^".repeat(line[start..end].chars().count().max(1));
+                format!(
+                    "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+                    error,
+                    gutter,
+                    path.display(),
+                    location,
+                    gutter,
+                    location.line,
+                    line,
+                    gutter,
+                    indent,
+                    carets
+                )
+            }
+            error => format!("error: {}\n", error),
+        }
+    }
+}
+
+/// Reads and evaluates the model in `path`, which has to result in a shape.
+///
+/// The model is the last shape picked with `(render ...)` in the file itself, or else the value
+/// of the last form that is a shape.
+///
+/// Errors are located at the innermost form they were found in, errors in a file pulled in with
+/// `include` in that file.
+pub fn load_shape(path: &Path) -> Result<CsgFunc, LoadError> {
+    load_shape_tracking(path, &mut vec![])
+}
+
+/// Same as `load_shape`, also adding every file that was read to `files`, the included ones
+/// too. Files are added even when loading fails, so they can be watched until it is fixed.
+pub fn load_shape_tracking(path: &Path, files: &mut Vec<PathBuf>) -> Result<CsgFunc, LoadError> {
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    env.open_file(path).expect("nothing is open in a new environment");
+    let result = eval_file(path, &env);
+    env.close_file();
+    files.extend(env.files_read());
+    let rendered = env.take_rendered();
+    // Drops whatever the environment still refers to, leaving the shape to us alone
+    env.clear();
+    drop(env);
+
+    let model = match (rendered, result?) {
+        (Some(shape), _) => Ty::CsgFunc(shape),
+        (None, ty) => ty,
+    };
+    match model {
+        Ty::CsgFunc(csg_func) => Ok(Arc::try_unwrap(csg_func)
+            .unwrap_or_else(|_| panic!("nothing but the model refers to the shape anymore"))),
+        _ => Err(LoadError::NotAShape(path.to_owned())),
+    }
+}
+
+/// Reads and evaluates the forms in the file at `path` in order, in `env`, see `lang::eval_file`.
+pub fn eval_file(path: &Path, env: &lang::Env) -> Result<Ty, LoadError> {
+    lang::eval_file(path, env).map_err(|error| match error {
+        lang::Error::Read(path, error) => LoadError::Io(path, error),
+        lang::Error::InFile { path, text, error } => LoadError::lang(&path, &text, *error),
+        // Found outside of any file
+        error => LoadError::lang(path, "", error),
+    })
+}
@@ -1,18 +1,42 @@
 use {
     argh::FromArgs,
-    conjure::{event_loop, lang, shape::CsgFunc},
-    log::info,
-    notify::{watcher, RecursiveMode, Watcher},
-    std::{path::PathBuf, sync::mpsc::channel, sync::Arc, time::Duration},
-    winit::{event_loop::EventLoop, platform::unix::WindowBuilderExtUnix, window::WindowBuilder},
+    conjure::{
+        event_loop::{self, UserEvent},
+        load::{self, LoadError},
+        mass::MassProperties,
+        mesh::validate,
+        mesher::{self, Mesher, MesherKind},
+        octree::{Octree, ShapeHandle},
+        progress::{Progress, Stage},
+        shape::CsgFunc,
+        types::Triangle,
+        watch::ModelWatcher,
+        worker::{self, Job, Worker},
+    },
+    log::{info, warn},
+    std::{
+        io::IsTerminal,
+        path::{Path, PathBuf},
+        process::ExitCode,
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    },
+    winit::{
+        event_loop::{EventLoop, EventLoopProxy},
+        platform::unix::WindowBuilderExtUnix,
+        window::WindowBuilder,
+    },
 };
 
 #[derive(FromArgs)]
 /// Conjure shapes.
 pub struct Arguments {
-    /// input file
+    #[argh(subcommand)]
+    command: Option<Command>,
+
+    /// input file to view
     #[argh(positional)]
-    input: PathBuf,
+    input: Option<PathBuf>,
 
     /// resolution of the rendered model
     #[argh(option)]
@@ -22,66 +46,217 @@ pub struct Arguments {
     /// (-bound .. bound)
     #[argh(option)]
     bound: f32,
+
+    /// maximum error allowed when simplifying the octree
+    /// (0 disables simplification, which marching-cubes requires)
+    #[argh(option, default = "0.0")]
+    tolerance: f32,
+
+    /// how the surface is extracted from the octree: dual-contouring,
+    /// marching-cubes or surface-nets
+    #[argh(option, default = "MesherKind::DualContouring")]
+    mesher: MesherKind,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Check(Check),
+    Stats(Stats),
+}
+
+#[derive(FromArgs)]
+/// Check whether the mesh of a shape is closed and manifold, without opening a window.
+#[argh(subcommand, name = "check")]
+struct Check {
+    /// input file
+    #[argh(positional)]
+    input: PathBuf,
 }
 
-fn eval_ast(input: PathBuf) -> Result<conjure::lang::Ty, Box<dyn std::error::Error>> {
-    // Slurp the contents of the file
-    let contents = std::fs::read_to_string(input)?;
+#[derive(FromArgs)]
+/// Print the volume, surface area, center of mass and inertia of a shape, measured on its mesh
+/// and on the shape itself.
+#[argh(subcommand, name = "stats")]
+struct Stats {
+    /// input file
+    #[argh(positional)]
+    input: PathBuf,
+
+    /// density of the material, in mass per unit of volume
+    #[argh(option, default = "1.0")]
+    density: f32,
+}
 
-    // Parse
-    let tokens = lang::Reader::read_str(&contents)?;
-    let env = lang::Env::new();
-    let core_ns = lang::Namespace::new();
-    for (sym, func) in core_ns.into_iter() {
-        env.register_sym(sym, func);
+/// Loads the model in `input` and hands it to the worker, or shows why it couldn't be loaded.
+///
+/// The previous model keeps rendering after an error, until the file is fixed. Returns the files
+/// the model was loaded from, which are the ones to watch.
+fn reload(input: &Path, worker: &Worker, proxy: &EventLoopProxy<UserEvent>) -> Vec<PathBuf> {
+    let mut files = vec![];
+    match load::load_shape_tracking(input, &mut files) {
+        Ok(csg_func) => {
+            let _ = proxy.send_event(UserEvent::Loaded);
+            let _ = worker.send(Job::Shape(csg_func));
+        }
+        Err(error) => {
+            eprint!("{}", error.diagnostic());
+            let _ = proxy.send_event(UserEvent::LoadFailed(error.to_string()));
+        }
     }
+    files
+}
 
-    // Eval
-    let ast = lang::eval(tokens, &env)?;
-    Ok(ast)
+/// Renders and meshes `csg_func` like the viewer would.
+fn mesh_shape(args: &Arguments, csg_func: &CsgFunc) -> (Octree, ShapeHandle, Vec<Triangle>) {
+    let mut octree = Octree::new(-args.bound, args.bound);
+    octree.set_tolerance(args.tolerance);
+    let progress = Progress::new();
+    octree.set_progress(progress.clone());
+    let (handle, faces) = with_progress_bar(&progress, || {
+        let handle = octree.render_shape(args.resolution, csg_func);
+        (handle, args.mesher.extract_shape_faces(&octree, handle))
+    });
+    let triangles = mesher::triangulate(&faces, csg_func);
+    (octree, handle, triangles)
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Runs `work` while drawing `progress` as a bar on stderr, if it is a terminal.
+fn with_progress_bar<T>(progress: &Progress, work: impl FnOnce() -> T) -> T {
+    if !std::io::stderr().is_terminal() {
+        return work();
+    }
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            const WIDTH: usize = 30;
+            while !done.load(Ordering::Relaxed) {
+                let stage = match progress.stage() {
+                    Stage::Idle => None,
+                    Stage::Rendering => Some("rendering octree"),
+                    Stage::Extracting => Some("extracting faces"),
+                };
+                if let Some(stage) = stage {
+                    let completion = progress.completion();
+                    let filled = (completion * WIDTH as f32) as usize;
+                    eprint!(
+                        "\r\x1b[2K[{}{}] {:>3}% {}, {} octants",
+                        "#".repeat(filled),
+                        "-".repeat(WIDTH - filled),
+                        (completion * 100.0) as u32,
+                        stage,
+                        progress.visited()
+                    );
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            eprint!("\r\x1b[2K");
+        });
+        let result = work();
+        done.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+/// Meshes the shape in `input` like the viewer would and reports any problems with the mesh.
+fn check(args: &Arguments, input: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let csg_func = load::load_shape(&input)?;
+    let (_, _, triangles) = mesh_shape(args, &csg_func);
+
+    let report = validate::validate(&triangles);
+    print!("{}", report);
+    if !report.is_valid() {
+        return Err("the mesh has problems".into());
+    }
+    Ok(())
+}
+
+/// Prints the mass properties of the shape in `input`, from its mesh and from the shape itself.
+fn stats(args: &Arguments, stats: &Stats) -> Result<(), Box<dyn std::error::Error>> {
+    let csg_func = load::load_shape(&stats.input)?;
+    let (octree, handle, triangles) = mesh_shape(args, &csg_func);
+
+    for (name, properties) in [
+        ("mesh", MassProperties::from_mesh(&triangles)),
+        ("shape", MassProperties::from_octree(&octree, handle, &csg_func)),
+    ] {
+        let centroid = properties.centroid;
+        let inertia = properties.inertia(stats.density);
+        println!("{}:", name);
+        println!("  volume: {}", properties.volume);
+        println!("  surface area: {}", properties.surface_area);
+        println!("  center of mass: ({}, {}, {})", centroid.x, centroid.y, centroid.z);
+        println!("  mass: {}", properties.mass(stats.density));
+        println!("  inertia:");
+        for row in inertia.row_iter() {
+            println!("    {:>12.4} {:>12.4} {:>12.4}", row[0], row[1], row[2]);
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
     env_logger::init();
     info!("starting up");
 
     // Read in command line args
     let args: Arguments = argh::from_env();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            match error.downcast_ref::<LoadError>() {
+                Some(error) => eprint!("{}", error.diagnostic()),
+                None => eprintln!("error: {}", error),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let event_loop = EventLoop::new();
+fn run(args: Arguments) -> Result<(), Box<dyn std::error::Error>> {
+    if args.tolerance > 0.0 && !args.mesher.supports_simplification() {
+        return Err(format!(
+            "the {} mesher leaves cracks in simplified octrees, use a tolerance of 0",
+            args.mesher
+        )
+        .into());
+    }
+
+    let input = match (args.command.as_ref(), args.input.clone()) {
+        (Some(Command::Check(Check { input })), _) => return check(&args, input.clone()),
+        (Some(Command::Stats(command)), _) => return stats(&args, command),
+        (None, Some(input)) => input,
+        (None, None) => return Err("missing input file".into()),
+    };
+
+    let event_loop = EventLoop::with_user_event();
     let proxy = event_loop.create_proxy();
     let window = WindowBuilder::new()
         .with_title("conjure")
         .with_app_id("conjure".to_string())
         .build(&event_loop)?;
 
-    let (tx, rx) = channel();
-    let (ast_sender, ast_recv) = channel();
+    let settings = worker::Settings {
+        resolution: args.resolution,
+        bound: args.bound,
+        tolerance: args.tolerance,
+        mesher: args.mesher,
+    };
+    let worker = worker::spawn(proxy.clone(), settings);
 
-    let mut watcher = watcher(tx, Duration::from_micros(4))?;
-    watcher.watch(args.input.parent().unwrap(), RecursiveMode::Recursive)?;
-
-    let ast = eval_ast(args.input.clone())?;
-    if let conjure::lang::Ty::CsgFunc(csg_func) = ast {
-        let csg_func: CsgFunc =
-            Arc::try_unwrap(csg_func).expect("No refrences to the ast should remain");
-        ast_sender.send(csg_func)?;
-        proxy.send_event(())?;
+    let mut watcher = ModelWatcher::new(Duration::from_millis(50))?;
+    // The directories that can be watched still are, the others are tried again on reload
+    if let Err(error) = watcher.set_files(&reload(&input, &worker, &proxy)) {
+        warn!("Could not watch the model: {}", error);
     }
 
-    let input = args.input.clone().canonicalize()?;
-    std::thread::spawn(move || loop {
-        if let Ok(notify::DebouncedEvent::Create(path)) = rx.recv() {
-            if let Ok(path) = path.canonicalize() {
-                if path == input {
-                    let ast = eval_ast(path).unwrap();
-                    if let conjure::lang::Ty::CsgFunc(csg_func) = ast {
-                        let csg_func: CsgFunc = Arc::try_unwrap(csg_func)
-                            .expect("No refrences to the ast should remain");
-                        let _ = ast_sender.send(csg_func);
-                        let _ = proxy.send_event(());
-                    }
-                }
+    let watch_worker = worker.clone();
+    std::thread::spawn(move || {
+        while let Some(path) = watcher.wait() {
+            info!("{} changed", path.display());
+            let files = reload(&input, &watch_worker, &proxy);
+            if let Err(error) = watcher.set_files(&files) {
+                warn!("Could not watch the model: {}", error);
             }
         }
     });
@@ -89,5 +264,5 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let depth = ((args.bound * 2.0) / args.resolution).log2() as u8;
     eprintln!("Rendering a shape at a resolution of {} (depth: {})", args.resolution, depth);
     // Render the shape
-    event_loop::start(window, event_loop, ast_recv, args.resolution, args.bound)
+    event_loop::start(window, event_loop, worker, args.resolution)
 }
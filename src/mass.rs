@@ -0,0 +1,237 @@
+use {
+    crate::{
+        octree::{OctAxis, Octant, Octree, ShapeHandle},
+        shape::CsgFunc,
+        types::{Point, Triangle},
+    },
+    nalgebra::{Matrix3, Vector3},
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Samples per axis taken in the smallest leaves when integrating the shape.
+const SUBSAMPLES: f32 = 4.0;
+
+/// Volume, surface area and how the volume is distributed, for a density of 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MassProperties {
+    pub volume: f32,
+    pub surface_area: f32,
+    pub centroid: Point,
+    /// Inertia tensor about the centroid.
+    pub inertia: Matrix3<f32>,
+}
+
+impl MassProperties {
+    /// Integrates over the volume enclosed by a closed mesh with the divergence theorem.
+    ///
+    /// `triangles` have to wind counter-clockwise seen from outside, like the meshers produce.
+    pub fn from_mesh(triangles: &[Triangle]) -> Self {
+        let mut moments = Moments::default();
+        let mut surface_area = 0.0;
+        for triangle in triangles {
+            let [p0, p1, p2] = triangle.map(|p| p.as_vector().cast::<f64>());
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            surface_area += normal.norm() / 2.0;
+
+            // Integrals of polynomials over the tetrahedron spanned with the origin, as terms of
+            // the surface integrals of the triangle (see Eberly, "Polyhedral Mass Properties")
+            let mut f2 = Vector3::zeros();
+            let mut f3 = Vector3::zeros();
+            let mut g = [Vector3::zeros(); 3];
+            for axis in 0..3 {
+                let (w0, w1, w2) = (p0[axis], p1[axis], p2[axis]);
+                let f1 = w0 + w1 + w2;
+                let squared = w0 * w0 + w1 * (w0 + w1);
+                f2[axis] = squared + w2 * f1;
+                f3[axis] = w0 * w0 * w0 + w1 * squared + w2 * f2[axis];
+                g[0][axis] = f2[axis] + w0 * (f1 + w0);
+                g[1][axis] = f2[axis] + w1 * (f1 + w1);
+                g[2][axis] = f2[axis] + w2 * (f1 + w2);
+            }
+
+            moments.volume += normal.x * (p0.x + p1.x + p2.x) / 6.0;
+            moments.first += normal.component_mul(&f2) / 24.0;
+            let mut second = Matrix3::from_diagonal(&(normal.component_mul(&f3) / 60.0));
+            let xy = normal.x * (p0.y * g[0].x + p1.y * g[1].x + p2.y * g[2].x) / 120.0;
+            let yz = normal.y * (p0.z * g[0].y + p1.z * g[1].y + p2.z * g[2].y) / 120.0;
+            let zx = normal.z * (p0.x * g[0].z + p1.x * g[1].z + p2.x * g[2].z) / 120.0;
+            second[(0, 1)] = xy;
+            second[(1, 0)] = xy;
+            second[(1, 2)] = yz;
+            second[(2, 1)] = yz;
+            second[(0, 2)] = zx;
+            second[(2, 0)] = zx;
+            moments.second += second;
+        }
+        moments.into_properties(surface_area)
+    }
+
+    /// Integrates the shape `handle` rendered into `tree` from `shape_func`, the function it was
+    /// rendered from.
+    ///
+    /// Leaves are split into boxes until they are clear of the surface, down to a grid finer
+    /// than the smallest leaf. Each box at the bottom covers the part of it the distance to the
+    /// surface puts inside, so the result converges faster than counting boxes as in or out.
+    pub fn from_octree(tree: &Octree, handle: ShapeHandle, shape_func: &CsgFunc) -> Self {
+        // One grid is used for all leaves, so the surface is spread out evenly over the boxes
+        let step =
+            match tree.shape_leaves(handle).map(|octant| octant.x_axis.length()).reduce(f32::min) {
+                Some(smallest) => smallest / SUBSAMPLES,
+                None => return Moments::default().into_properties(0.0),
+            };
+        let leaves: Vec<&Octant> = tree.shape_leaves(handle).collect();
+        let integrate = |octant: &&Octant| {
+            let mut moments = Moments::default();
+            let mut surface_area = 0.0;
+            let (x, y, z) = (octant.x_axis, octant.y_axis, octant.z_axis);
+            integrate_box(x, y, z, step, shape_func, &mut moments, &mut surface_area);
+            (moments, surface_area)
+        };
+
+        #[cfg(feature = "parallel")]
+        let integrated: Vec<(Moments, f64)> = leaves.par_iter().map(integrate).collect();
+        #[cfg(not(feature = "parallel"))]
+        let integrated: Vec<(Moments, f64)> = leaves.iter().map(integrate).collect();
+
+        // Summed in the order of the leaves, so the result doesn't depend on the threads
+        let mut moments = Moments::default();
+        let mut surface_area = 0.0;
+        for (leaf, area) in integrated {
+            moments += leaf;
+            surface_area += area;
+        }
+        moments.into_properties(surface_area)
+    }
+
+    /// Mass of the shape when made out of a material of `density`.
+    pub fn mass(&self, density: f32) -> f32 {
+        self.volume * density
+    }
+
+    /// Inertia tensor about the centroid for a material of `density`.
+    pub fn inertia(&self, density: f32) -> Matrix3<f32> {
+        self.inertia * density
+    }
+}
+
+/// Adds the part of the box within the shape to `moments` and the surface crossing it to
+/// `surface_area`, splitting the box while the surface could be close and it is larger than
+/// `step`.
+fn integrate_box(
+    x: OctAxis,
+    y: OctAxis,
+    z: OctAxis,
+    step: f32,
+    shape_func: &CsgFunc,
+    moments: &mut Moments,
+    surface_area: &mut f64,
+) {
+    let size = x.length();
+    let distance = distance(x.center(), y.center(), z.center(), size, shape_func);
+
+    if size > step * 1.5 {
+        // The surface is spread over a box on either side of it
+        let reach = size * 3.0_f32.sqrt() / 2.0 + step;
+        if distance.abs() < reach {
+            let (x0, x1) = x.split();
+            let (y0, y1) = y.split();
+            let (z0, z1) = z.split();
+            for x in [x0, x1] {
+                for y in [y0, y1] {
+                    for z in [z0, z1] {
+                        integrate_box(x, y, z, step, shape_func, moments, surface_area);
+                    }
+                }
+            }
+        } else if distance < 0.0 {
+            moments.add_box(x, y, z, 1.0);
+        }
+        return;
+    }
+
+    // The box is filled up to the surface, which is as wide as the box around it
+    let filled = (0.5 - distance / size).clamp(0.0, 1.0);
+    moments.add_box(x, y, z, filled as f64);
+    let volume = (x.length() * y.length() * z.length()) as f64;
+    *surface_area += volume * (1.0 - (distance / size).abs() as f64).max(0.0) / size as f64;
+}
+
+/// Distance to the surface from a point, also for shapes that are not exact distance fields.
+///
+/// `size` is the scale the gradient is measured over.
+fn distance(x: f32, y: f32, z: f32, size: f32, shape_func: &CsgFunc) -> f32 {
+    let value = shape_func.call(x, y, z);
+    let delta = size / 8.0;
+    let gradient = Vector3::new(
+        shape_func.call(x + delta, y, z) - shape_func.call(x - delta, y, z),
+        shape_func.call(x, y + delta, z) - shape_func.call(x, y - delta, z),
+        shape_func.call(x, y, z + delta) - shape_func.call(x, y, z - delta),
+    ) / (2.0 * delta);
+    match gradient.norm() {
+        norm if norm > f32::EPSILON => value / norm,
+        _ => value,
+    }
+}
+
+/// Integrals of 1, `r` and `r rᵀ` over a volume.
+#[derive(Debug, Clone, Copy)]
+struct Moments {
+    volume: f64,
+    first: Vector3<f64>,
+    second: Matrix3<f64>,
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Moments { volume: 0.0, first: Vector3::zeros(), second: Matrix3::zeros() }
+    }
+}
+
+impl std::ops::AddAssign for Moments {
+    fn add_assign(&mut self, other: Self) {
+        self.volume += other.volume;
+        self.first += other.first;
+        self.second += other.second;
+    }
+}
+
+impl Moments {
+    /// Adds a box, of which `filled` is taken up by the shape spread out evenly.
+    fn add_box(&mut self, x: OctAxis, y: OctAxis, z: OctAxis, filled: f64) {
+        let axes = [x, y, z].map(|axis| (axis.lower as f64, axis.upper as f64));
+        let length = Vector3::from_fn(|i, _| axes[i].1 - axes[i].0);
+        // Integrals of x and x² along each axis
+        let first = Vector3::from_fn(|i, _| (axes[i].1.powi(2) - axes[i].0.powi(2)) / 2.0);
+        let squared = Vector3::from_fn(|i, _| (axes[i].1.powi(3) - axes[i].0.powi(3)) / 3.0);
+
+        let volume = length.x * length.y * length.z;
+        self.volume += filled * volume;
+        self.first += filled * first.component_div(&length) * volume;
+        let mut second = first * first.transpose() * volume;
+        for i in 0..3 {
+            for j in 0..3 {
+                second[(i, j)] /= length[i] * length[j];
+            }
+            second[(i, i)] = squared[i] / length[i] * volume;
+        }
+        self.second += filled * second;
+    }
+
+    fn into_properties(self, surface_area: f64) -> MassProperties {
+        let centroid = match self.volume {
+            volume if volume != 0.0 => self.first / volume,
+            _ => Vector3::zeros(),
+        };
+        // Move the second moment to the centroid, then I = tr(S) 1 - S
+        let second = self.second - self.volume * centroid * centroid.transpose();
+        let inertia = Matrix3::identity() * second.trace() - second;
+        MassProperties {
+            volume: self.volume as f32,
+            surface_area: surface_area as f32,
+            centroid: Point::new(centroid.x as f32, centroid.y as f32, centroid.z as f32),
+            inertia: inertia.cast::<f32>(),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use {
+    crate::types::{Point, Triangle},
+    nalgebra::Vector3,
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+    },
+};
+
+/// Number of locations kept for every kind of problem.
+const SAMPLES: usize = 5;
+
+/// Problems of one kind found in a mesh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Issue {
+    pub count: usize,
+    /// Where the first few problems are.
+    pub samples: Vec<Point>,
+}
+
+impl Issue {
+    fn add(&mut self, at: Point) {
+        self.count += 1;
+        if self.samples.len() < SAMPLES {
+            self.samples.push(at);
+        }
+    }
+}
+
+/// Everything keeping a mesh from being printed, from `validate`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub triangles: usize,
+    /// Edges used by a single triangle, the mesh has holes.
+    pub boundary_edges: Issue,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: Issue,
+    /// Vertices where the triangles around them don't form a single fan.
+    pub non_manifold_vertices: Issue,
+    /// Triangles without area.
+    pub degenerate_triangles: Issue,
+    /// Edges between two triangles winding the opposite way.
+    pub flipped_normals: Issue,
+    pub self_intersections: Issue,
+}
+
+impl Report {
+    /// Whether the mesh is closed and every edge is shared by exactly two triangles.
+    pub fn is_watertight(&self) -> bool {
+        self.boundary_edges.count == 0 && self.non_manifold_edges.count == 0
+    }
+
+    /// Whether no problems were found at all.
+    pub fn is_valid(&self) -> bool {
+        self.is_watertight()
+            && self.non_manifold_vertices.count == 0
+            && self.degenerate_triangles.count == 0
+            && self.flipped_normals.count == 0
+            && self.self_intersections.count == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "triangles: {}", self.triangles)?;
+        for (name, issue) in [
+            ("boundary edges", &self.boundary_edges),
+            ("non-manifold edges", &self.non_manifold_edges),
+            ("non-manifold vertices", &self.non_manifold_vertices),
+            ("degenerate triangles", &self.degenerate_triangles),
+            ("flipped normals", &self.flipped_normals),
+            ("self-intersections", &self.self_intersections),
+        ] {
+            write!(f, "{}: {}", name, issue.count)?;
+            for (i, p) in issue.samples.iter().enumerate() {
+                let separator = if i == 0 { " at" } else { "," };
+                write!(f, "{} ({}, {}, {})", separator, p.x, p.y, p.z)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks whether `triangles` form a closed, manifold and consistently oriented surface that
+/// doesn't cross itself.
+///
+/// Vertices are matched by their exact position, which is how the meshers share them between
+/// faces.
+pub fn validate(triangles: &[Triangle]) -> Report {
+    let mut report = Report { triangles: triangles.len(), ..Default::default() };
+
+    let mut positions = vec![];
+    let mut ids = HashMap::new();
+    let indexed: Vec<[usize; 3]> = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|p| {
+                // -0.0 and 0.0 are the same vertex
+                let key = ((p.x + 0.0).to_bits(), (p.y + 0.0).to_bits(), (p.z + 0.0).to_bits());
+                *ids.entry(key).or_insert_with(|| {
+                    positions.push(p);
+                    positions.len() - 1
+                })
+            })
+        })
+        .collect();
+
+    for triangle in triangles {
+        let [a, b, c] = triangle.map(|p| p.as_vector());
+        let longest = [b - a, c - b, a - c].iter().map(|e| e.norm_squared()).fold(0.0, f32::max);
+        if (b - a).cross(&(c - a)).norm() <= f32::EPSILON * longest {
+            report.degenerate_triangles.add(centroid(triangle));
+        }
+    }
+
+    // Directions every edge is used in, keyed by its vertices in ascending order
+    let mut edges: HashMap<(usize, usize), Vec<bool>> = HashMap::new();
+    for &[a, b, c] in &indexed {
+        for (v0, v1) in [(a, b), (b, c), (c, a)] {
+            if v0 != v1 {
+                edges.entry((v0.min(v1), v0.max(v1))).or_default().push(v0 < v1);
+            }
+        }
+    }
+    let mut edge_list: Vec<_> = edges.into_iter().collect();
+    edge_list.sort_unstable_by_key(|&(edge, _)| edge);
+    for ((v0, v1), uses) in edge_list {
+        let at = midpoint(positions[v0], positions[v1]);
+        match uses.len() {
+            1 => report.boundary_edges.add(at),
+            2 if uses[0] == uses[1] => report.flipped_normals.add(at),
+            2 => {}
+            _ => report.non_manifold_edges.add(at),
+        }
+    }
+
+    non_manifold_vertices(&indexed, &positions, &mut report.non_manifold_vertices);
+    self_intersections(triangles, &indexed, &mut report.self_intersections);
+    report
+}
+
+/// Finds vertices where the triangles around them fall apart into separate fans.
+fn non_manifold_vertices(indexed: &[[usize; 3]], positions: &[Point], issue: &mut Issue) {
+    let mut around: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+    for (t, triangle) in indexed.iter().enumerate() {
+        for &v in triangle {
+            if !around[v].contains(&t) {
+                around[v].push(t);
+            }
+        }
+    }
+
+    for (v, triangles) in around.iter().enumerate() {
+        // Triangles sharing an edge through `v` are in the same fan
+        let mut fan: Vec<usize> = (0..triangles.len()).collect();
+        for i in 0..triangles.len() {
+            for j in i + 1..triangles.len() {
+                let (a, b) = (&indexed[triangles[i]], &indexed[triangles[j]]);
+                if a.iter().any(|&w| w != v && b.contains(&w)) {
+                    let (ri, rj) = (root(&mut fan, i), root(&mut fan, j));
+                    fan[ri] = rj;
+                }
+            }
+        }
+        let fans = (0..triangles.len()).filter(|&i| root(&mut fan, i) == i).count();
+        if fans > 1 {
+            issue.add(positions[v]);
+        }
+    }
+}
+
+/// Representative of the fan `i` belongs to, fans are kept as a union-find forest.
+fn root(fan: &mut [usize], mut i: usize) -> usize {
+    while fan[i] != i {
+        fan[i] = fan[fan[i]];
+        i = fan[i];
+    }
+    i
+}
+
+/// Finds pairs of triangles without a shared vertex that cross each other.
+///
+/// Triangles are bucketed into a grid sized after the average triangle, so only triangles close
+/// to each other are tested.
+fn self_intersections(triangles: &[Triangle], indexed: &[[usize; 3]], issue: &mut Issue) {
+    if triangles.is_empty() {
+        return;
+    }
+    let bounds: Vec<(Vector3<f32>, Vector3<f32>)> = triangles
+        .iter()
+        .map(|triangle| {
+            let [a, b, c] = triangle.map(|p| p.as_vector());
+            (a.inf(&b).inf(&c), a.sup(&b).sup(&c))
+        })
+        .collect();
+    let size = bounds.iter().map(|(lower, upper)| (upper - lower).max()).sum::<f32>()
+        / triangles.len() as f32;
+    if size <= 0.0 {
+        return;
+    }
+    let cell = |v: Vector3<f32>| v.map(|c| (c / size).floor() as i64);
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    for (t, (lower, upper)) in bounds.iter().enumerate() {
+        let (lower, upper) = (cell(*lower), cell(*upper));
+        for x in lower.x..=upper.x {
+            for y in lower.y..=upper.y {
+                for z in lower.z..=upper.z {
+                    grid.entry((x, y, z)).or_default().push(t);
+                }
+            }
+        }
+    }
+
+    let mut cells: Vec<_> = grid.into_iter().collect();
+    cells.sort_unstable_by_key(|&(at, _)| at);
+    let mut tested = HashSet::new();
+    for (_, in_cell) in cells {
+        for (i, &t0) in in_cell.iter().enumerate() {
+            for &t1 in &in_cell[i + 1..] {
+                let (b0, b1) = (bounds[t0], bounds[t1]);
+                let overlap =
+                    (0..3).all(|axis| b0.0[axis] <= b1.1[axis] && b1.0[axis] <= b0.1[axis]);
+                let shares_vertex = indexed[t0].iter().any(|v| indexed[t1].contains(v));
+                if !overlap || shares_vertex || !tested.insert((t0, t1)) {
+                    continue;
+                }
+                if let Some(at) = intersection(&triangles[t0], &triangles[t1]) {
+                    issue.add(at);
+                }
+            }
+        }
+    }
+}
+
+/// Where two triangles cross, if an edge of one passes through the other.
+fn intersection(t0: &Triangle, t1: &Triangle) -> Option<Point> {
+    let crossing = |edges: &Triangle, triangle: &Triangle| {
+        (0..3).find_map(|i| segment_triangle(edges[i], edges[(i + 1) % 3], triangle))
+    };
+    crossing(t0, t1).or_else(|| crossing(t1, t0))
+}
+
+/// Where the segment from `p0` to `p1` passes through the inside of `triangle`.
+fn segment_triangle(p0: Point, p1: Point, triangle: &Triangle) -> Option<Point> {
+    let [a, b, c] = triangle.map(|p| p.as_vector());
+    let (origin, direction) = (p0.as_vector(), p1.as_vector() - p0.as_vector());
+    let (e1, e2) = (b - a, c - a);
+    let h = direction.cross(&e2);
+    let det = e1.dot(&h);
+    // Parallel to the triangle, touching in its plane doesn't count as crossing
+    if det.abs() <= f32::EPSILON * e1.norm() * e2.norm() * direction.norm() {
+        return None;
+    }
+    let s = origin - a;
+    let u = s.dot(&h) / det;
+    let q = s.cross(&e1);
+    let v = direction.dot(&q) / det;
+    let t = e2.dot(&q) / det;
+    // Stay clear of the borders so neighbouring triangles touching along an edge don't count
+    const MARGIN: f32 = 1e-5;
+    if u > MARGIN && v > MARGIN && u + v < 1.0 - MARGIN && t > MARGIN && t < 1.0 - MARGIN {
+        let at = origin + direction * t;
+        Some(Point::new(at.x, at.y, at.z))
+    } else {
+        None
+    }
+}
+
+fn centroid([a, b, c]: &Triangle) -> Point {
+    Point::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0, (a.z + b.z + c.z) / 3.0)
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0, (a.z + b.z) / 2.0)
+}
@@ -0,0 +1,274 @@
+use {
+    crate::{
+        dual_contour,
+        octree::{Feature, Octant, Octree, ShapeHandle},
+        shape::CsgFunc,
+        types::{Aabb, Face, Point, Triangle},
+    },
+    std::{fmt, str::FromStr},
+};
+
+/// Turns the shapes rendered into an `Octree` into faces.
+///
+/// Faces wind counter-clockwise when seen from outside of the shape.
+pub trait Mesher {
+    /// Extracts the faces of the shape `handle`, none if there is no such shape.
+    fn extract_shape_faces(&self, tree: &Octree, handle: ShapeHandle) -> Vec<Face>;
+
+    /// Extracts the faces of the shape `handle` built from cells whose combined box overlaps
+    /// `region`, along with that box. Used to update the parts of a mesh that changed.
+    fn extract_shape_faces_in(
+        &self,
+        tree: &Octree,
+        handle: ShapeHandle,
+        region: &Aabb,
+    ) -> Vec<(Face, Aabb)>;
+
+    /// Extracts the faces of every shape in the octree.
+    fn extract_faces(&self, tree: &Octree) -> Vec<Face> {
+        tree.handles().flat_map(|handle| self.extract_shape_faces(tree, handle)).collect()
+    }
+
+    /// Whether the mesh stays closed when the octree is simplified (see
+    /// `Octree::set_tolerance`), which puts leaves of different sizes next to each other along
+    /// the surface.
+    fn supports_simplification(&self) -> bool {
+        true
+    }
+}
+
+/// Places one vertex per sheet of surface in every cell at the minimizer of its QEF, keeping
+/// sharp edges and corners. Faces are built across every edge crossing the surface.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DualContouring;
+
+impl Mesher for DualContouring {
+    fn extract_shape_faces(&self, tree: &Octree, handle: ShapeHandle) -> Vec<Face> {
+        match tree.shape_root(handle) {
+            Some(idx) => dual_contour::cell_proc(tree, idx, |feature| feature.point),
+            None => vec![],
+        }
+    }
+
+    fn extract_shape_faces_in(
+        &self,
+        tree: &Octree,
+        handle: ShapeHandle,
+        region: &Aabb,
+    ) -> Vec<(Face, Aabb)> {
+        match tree.shape_root(handle) {
+            Some(idx) => {
+                dual_contour::cell_proc_in(tree, idx, |feature| feature.point, Some(region))
+            }
+            None => vec![],
+        }
+    }
+}
+
+/// Same topology as dual contouring, but the vertex of each cell is the average of its surface
+/// crossings. Sharp features are rounded off, in exchange the surface is smooth and never folds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SurfaceNets;
+
+impl Mesher for SurfaceNets {
+    fn extract_shape_faces(&self, tree: &Octree, handle: ShapeHandle) -> Vec<Face> {
+        match tree.shape_root(handle) {
+            Some(idx) => dual_contour::cell_proc(tree, idx, |feature| feature.qef.mass_point()),
+            None => vec![],
+        }
+    }
+
+    fn extract_shape_faces_in(
+        &self,
+        tree: &Octree,
+        handle: ShapeHandle,
+        region: &Aabb,
+    ) -> Vec<(Face, Aabb)> {
+        let placement = |feature: &Feature| feature.qef.mass_point();
+        match tree.shape_root(handle) {
+            Some(idx) => dual_contour::cell_proc_in(tree, idx, placement, Some(region)),
+            None => vec![],
+        }
+    }
+}
+
+/// Builds a polygon in every leaf out of the surface crossings on its edges.
+///
+/// Ambiguous faces are resolved the same way on both sides, so the mesh is manifold as long as
+/// the leaves along the surface share one size. Without simplification they always do: leaves
+/// are only merged into larger ones where the shape has the same sign at every corner of the
+/// finest level, so no surface reaches their faces. Simplified octrees would crack where a
+/// collapsed leaf meets smaller ones, so this mesher does not support them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarchingCubes;
+
+impl Mesher for MarchingCubes {
+    fn extract_shape_faces(&self, tree: &Octree, handle: ShapeHandle) -> Vec<Face> {
+        let mut faces = vec![];
+        for octant in tree.shape_leaves(handle) {
+            leaf_faces(octant, &mut faces);
+        }
+        faces
+    }
+
+    fn extract_shape_faces_in(
+        &self,
+        tree: &Octree,
+        handle: ShapeHandle,
+        region: &Aabb,
+    ) -> Vec<(Face, Aabb)> {
+        let mut bounded = vec![];
+        for octant in tree.shape_leaves(handle).filter(|o| o.bounds().overlaps(region)) {
+            let mut faces = vec![];
+            leaf_faces(octant, &mut faces);
+            bounded.extend(faces.into_iter().map(|face| (face, octant.bounds())));
+        }
+        bounded
+    }
+
+    fn supports_simplification(&self) -> bool {
+        false
+    }
+}
+
+/// Adds the marching cubes polygons of a leaf to `faces`.
+fn leaf_faces(octant: &Octant, faces: &mut Vec<Face>) {
+    let inside = octant.inside();
+    for edges in dual_contour::surface_components(inside) {
+        // The loop already winds counter-clockwise seen from outside of the shape
+        let points: Vec<Point> = dual_contour::edge_loop(inside, edges)
+            .into_iter()
+            .filter_map(|edge| octant.hermite[edge].map(|hermite| hermite.point))
+            .collect();
+        // Fan out from the first crossing of the loop
+        for pair in points.windows(2).skip(1) {
+            faces.push(Face::Triangle { ul: points[0], lr: pair[0], ll: pair[1] });
+        }
+    }
+}
+
+/// Splits faces into triangles, keeping their winding.
+///
+/// Quads from dual contouring are rarely planar, so the diagonal they are split along decides
+/// which way they fold. See `split_quad`.
+pub fn triangulate<'a>(
+    faces: impl IntoIterator<Item = &'a Face>,
+    csg_func: &CsgFunc,
+) -> Vec<Triangle> {
+    let faces = faces.into_iter();
+    let mut triangles = Vec::with_capacity(faces.size_hint().0 * 2);
+    for face in faces {
+        match *face {
+            Face::Triangle { ul, lr, ll } => triangles.push([ul, lr, ll]),
+            Face::Plane { ul, ur, ll, lr } => {
+                triangles.extend_from_slice(&split_quad(ul, ur, ll, lr, csg_func))
+            }
+        }
+    }
+    triangles
+}
+
+/// Splits the quad going around `ul`, `ur`, `lr`, `ll` along the diagonal whose midpoint lies
+/// closest to the surface of `csg_func`. When both fit the surface as well, the diagonal giving
+/// the smallest dihedral angle between the two triangles is used.
+pub fn split_quad(ul: Point, ur: Point, ll: Point, lr: Point, csg_func: &CsgFunc) -> [Triangle; 2] {
+    let across_ul_lr = [[ul, ur, lr], [lr, ll, ul]];
+    let across_ur_ll = [[ur, lr, ll], [ll, ul, ur]];
+
+    let distance = |a: Point, b: Point| {
+        let midpoint = (a.as_vector() + b.as_vector()) / 2.0;
+        csg_func.call(midpoint.x, midpoint.y, midpoint.z).abs()
+    };
+    let (ul_lr, ur_ll) = (distance(ul, lr), distance(ur, ll));
+    // Distances this close are noise from sampling a flat part of the surface
+    let tie = 1e-3 * f32::min((lr - ul).as_vector().norm(), (ll - ur).as_vector().norm());
+    if (ul_lr - ur_ll).abs() > tie {
+        return if ul_lr < ur_ll { across_ul_lr } else { across_ur_ll };
+    }
+
+    if fold(&across_ul_lr) >= fold(&across_ur_ll) {
+        across_ul_lr
+    } else {
+        across_ur_ll
+    }
+}
+
+/// Cosine of the angle between the normals of two triangles, 1 when they lie in one plane.
+fn fold([a, b]: &[Triangle; 2]) -> f32 {
+    let normal = |[p0, p1, p2]: &Triangle| {
+        (*p1 - *p0).as_vector().cross(&(*p2 - *p0).as_vector()).try_normalize(f32::EPSILON)
+    };
+    match (normal(a), normal(b)) {
+        (Some(a), Some(b)) => a.dot(&b),
+        _ => -1.0,
+    }
+}
+
+/// The available meshers, used to pick one from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MesherKind {
+    #[default]
+    DualContouring,
+    MarchingCubes,
+    SurfaceNets,
+}
+
+impl Mesher for MesherKind {
+    fn extract_shape_faces(&self, tree: &Octree, handle: ShapeHandle) -> Vec<Face> {
+        match self {
+            MesherKind::DualContouring => DualContouring.extract_shape_faces(tree, handle),
+            MesherKind::MarchingCubes => MarchingCubes.extract_shape_faces(tree, handle),
+            MesherKind::SurfaceNets => SurfaceNets.extract_shape_faces(tree, handle),
+        }
+    }
+
+    fn extract_shape_faces_in(
+        &self,
+        tree: &Octree,
+        handle: ShapeHandle,
+        region: &Aabb,
+    ) -> Vec<(Face, Aabb)> {
+        match self {
+            MesherKind::DualContouring => {
+                DualContouring.extract_shape_faces_in(tree, handle, region)
+            }
+            MesherKind::MarchingCubes => MarchingCubes.extract_shape_faces_in(tree, handle, region),
+            MesherKind::SurfaceNets => SurfaceNets.extract_shape_faces_in(tree, handle, region),
+        }
+    }
+
+    fn supports_simplification(&self) -> bool {
+        match self {
+            MesherKind::DualContouring => DualContouring.supports_simplification(),
+            MesherKind::MarchingCubes => MarchingCubes.supports_simplification(),
+            MesherKind::SurfaceNets => SurfaceNets.supports_simplification(),
+        }
+    }
+}
+
+impl FromStr for MesherKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dual-contouring" => Ok(MesherKind::DualContouring),
+            "marching-cubes" => Ok(MesherKind::MarchingCubes),
+            "surface-nets" => Ok(MesherKind::SurfaceNets),
+            _ => Err(format!(
+                "unknown mesher `{}`, expected dual-contouring, marching-cubes or surface-nets",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MesherKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MesherKind::DualContouring => "dual-contouring",
+            MesherKind::MarchingCubes => "marching-cubes",
+            MesherKind::SurfaceNets => "surface-nets",
+        };
+        write!(f, "{}", name)
+    }
+}
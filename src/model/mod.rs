@@ -1,6 +1,8 @@
 pub mod faces;
 pub mod octants;
+pub mod overlay;
 pub mod points;
+pub mod text;
 
 pub trait Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -1,8 +1,34 @@
 use {
-    crate::{model::ModelVertex, octree::Octant, util},
+    crate::{
+        model::ModelVertex,
+        octree::{OctAxis, Octant},
+        types::Point,
+        util,
+    },
     wgpu::util::DeviceExt,
 };
 
+/// What the outline of a leaf octant is drawn from, without the rest of the octant.
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    pub x_axis: OctAxis,
+    pub y_axis: OctAxis,
+    pub z_axis: OctAxis,
+    /// First feature point of the octant, coloring the outline.
+    pub point: Option<Point>,
+}
+
+impl From<&Octant> for Outline {
+    fn from(octant: &Octant) -> Self {
+        Outline {
+            x_axis: octant.x_axis,
+            y_axis: octant.y_axis,
+            z_axis: octant.z_axis,
+            point: octant.feature_points().next(),
+        }
+    }
+}
+
 pub struct OctantMesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
@@ -11,18 +37,18 @@ pub struct OctantMesh {
 }
 
 impl OctantMesh {
-    pub fn new(device: &wgpu::Device, _queue: &wgpu::Queue, octants: &[Octant]) -> Self {
+    pub fn new(device: &wgpu::Device, _queue: &wgpu::Queue, outlines: &[Outline]) -> Self {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
 
-        for octant in octants {
-            let color = match octant.feature {
+        for outline in outlines {
+            let color = match outline.point {
                 Some(p) => util::color_from_point(&p),
                 None => [0.9, 0.9, 0.9],
             };
-            let x_axis = octant.x_axis;
-            let y_axis = octant.y_axis;
-            let z_axis = octant.z_axis;
+            let x_axis = outline.x_axis;
+            let y_axis = outline.y_axis;
+            let z_axis = outline.z_axis;
 
             for face in &[
                 (
@@ -0,0 +1,64 @@
+use {crate::model::ModelVertex, wgpu::util::DeviceExt};
+
+/// Rectangle drawn over the scene, in normalized device coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct Rectangle {
+    pub lower: [f32; 2],
+    pub upper: [f32; 2],
+    pub color: [f32; 3],
+}
+
+pub struct OverlayMesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+}
+
+impl OverlayMesh {
+    /// Rectangles are drawn in order, later ones on top.
+    pub fn new(device: &wgpu::Device, _queue: &wgpu::Queue, rectangles: &[Rectangle]) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+
+        for Rectangle { lower, upper, color } in rectangles {
+            let start = vertices.len() as u32;
+            for [x, y] in [*lower, [upper[0], lower[1]], *upper, [lower[0], upper[1]]] {
+                vertices.push(ModelVertex { position: [x, y, 0.0], color: *color });
+            }
+            indices.extend_from_slice(&[start, start + 1, start + 2, start + 2, start + 3, start]);
+        }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        OverlayMesh {
+            name: String::from("Overlay Mesh"),
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+        }
+    }
+}
+
+pub trait DrawOverlay<'a> {
+    fn draw_overlay(&mut self, overlay: &'a OverlayMesh);
+}
+
+impl<'a, 'b> DrawOverlay<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_overlay(&mut self, overlay: &'a OverlayMesh) {
+        self.set_vertex_buffer(0, overlay.vertex_buffer.slice(..));
+        self.set_index_buffer(overlay.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.draw_indexed(0..overlay.num_elements, 0, 0..1);
+    }
+}
@@ -1,20 +1,28 @@
 use {
     crate::{
         dual_contour,
+        progress::{Progress, Stage, Tally},
+        qef::Qef,
         shape::CsgFunc,
-        types::{Face, Point},
+        types::{Aabb, Face, Point},
     },
-    log::warn,
-    parking_lot::Mutex,
+    nalgebra::Vector3,
+    smallvec::SmallVec,
 };
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+/// Subtrees at least this deep are built in parallel, each into its own arena.
+#[cfg(feature = "parallel")]
+const PARALLEL_DEPTH: u8 = 3;
+
 /// Index into the Octree for a unique `Octant`
 pub type OctantIdx = usize;
 
 /// Handle to an object (created from a `CsgFunc`) in the Octree.
+///
+/// Handles stay valid until their shape is removed and are never reused within one Octree.
 pub type ShapeHandle = usize;
 
 /// Axis (range) of the Octree
@@ -26,7 +34,7 @@ pub struct OctAxis {
 }
 
 impl OctAxis {
-    fn new(l: f32, u: f32) -> Self {
+    pub fn new(l: f32, u: f32) -> Self {
         let (lower, upper) = if l <= u { (l, u) } else { (u, l) };
         Self { lower, upper }
     }
@@ -43,49 +51,188 @@ impl OctAxis {
     pub fn length(&self) -> f32 {
         self.upper - self.lower
     }
+
+    /// Returns a bool based on if `value` lies within the axis.
+    pub fn contains(self, value: f32) -> bool {
+        (self.lower..=self.upper).contains(&value)
+    }
+
+    /// Restricts `value` to the range of the axis.
+    pub fn clamp(self, value: f32) -> f32 {
+        value.clamp(self.lower, self.upper)
+    }
 }
 
+/// Intersection of the surface with an edge of an `Octant`, along with the surface normal there.
+#[derive(Debug, Clone, Copy)]
+pub struct HermitePoint {
+    pub point: Point,
+    pub normal: Vector3<f32>,
+}
+
+/// Vertex of the dual mesh for one sheet of the surface passing through an `Octant`.
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub point: Point,
+    /// Bitmask of the octant edges (see `dual_contour::EDGES`) crossed by this sheet.
+    pub edges: u16,
+    pub qef: Qef,
+}
+
+/// Feature points of an `Octant`, almost always a single one.
+pub type Features = SmallVec<[Feature; 1]>;
+
 /// Represents each individual octant in the greater octree.
 #[derive(Debug, Clone)]
 pub struct Octant {
     pub x_axis: OctAxis,
     pub y_axis: OctAxis,
     pub z_axis: OctAxis,
+    /// Shape the octant, and the surface crossing it, belongs to. Each shape has a subtree of
+    /// its own, so every feature of the octant belongs to this shape.
+    pub shape: ShapeHandle,
     pub children: Option<[OctantIdx; 8]>,
-    pub feature: Option<Point>,
+    pub features: Features,
+    /// Value of the shape at every corner (see `dual_contour::corner_point`).
+    pub corners: [f32; 8],
+    /// Surface crossing of every edge with a sign change, only stored in leaf nodes.
+    pub hermite: [Option<HermitePoint>; 12],
 }
 
 #[allow(dead_code)]
 impl Octant {
-    /// Creates a new leaf node octant.
-    fn new(x_axis: OctAxis, y_axis: OctAxis, z_axis: OctAxis, feature: Option<Point>) -> Octant {
-        Self { x_axis, y_axis, z_axis, children: None, feature }
+    /// Creates a new octant without any surface crossing it.
+    fn new(x_axis: OctAxis, y_axis: OctAxis, z_axis: OctAxis, corners: [f32; 8]) -> Octant {
+        Self {
+            x_axis,
+            y_axis,
+            z_axis,
+            shape: 0,
+            children: None,
+            features: Features::new(),
+            corners,
+            hermite: [None; 12],
+        }
+    }
+
+    /// Creates a new leaf node octant, locating the surface from the values at its `corners`.
+    fn leaf(
+        x_axis: OctAxis,
+        y_axis: OctAxis,
+        z_axis: OctAxis,
+        corners: [f32; 8],
+        shape_func: &CsgFunc,
+    ) -> Octant {
+        let mut octant = Self::new(x_axis, y_axis, z_axis, corners);
+        if octant.inside() != 0 && octant.inside() != 0xff {
+            octant.hermite =
+                dual_contour::hermite_edges(x_axis, y_axis, z_axis, corners, shape_func);
+            octant.features = dual_contour::features_from_hermite(
+                x_axis,
+                y_axis,
+                z_axis,
+                octant.inside(),
+                &octant.hermite,
+            );
+        }
+        octant
+    }
+
+    /// Bitmask of the corners within the shape.
+    pub fn inside(&self) -> u8 {
+        dual_contour::inside_mask(&self.corners)
     }
 
     /// Returns a bool based on if the `Octant` contains a feature point.
     pub fn has_feature(&self) -> bool {
-        self.feature.is_some()
+        !self.features.is_empty()
+    }
+
+    /// Returns the feature representing the surface crossing on `edge` of the `Octant`.
+    pub fn feature_for_edge(&self, edge: usize) -> Option<&Feature> {
+        self.features.iter().find(|f| f.edges & (1 << edge) != 0)
+    }
+
+    /// Returns the feature points of every sheet of surface in the `Octant`.
+    pub fn feature_points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.features.iter().map(|f| f.point)
+    }
+
+    /// All the hermite data of a leaf `Octant`, internal nodes have none.
+    pub fn qef(&self) -> Option<Qef> {
+        if !self.is_leaf() {
+            return None;
+        }
+        let mut qef = Qef::new();
+        for feature in &self.features {
+            qef.merge(&feature.qef);
+        }
+        Some(qef)
     }
 
     /// Returns a bool based on if the Octant is a leaf node or not.
     pub fn is_leaf(&self) -> bool {
         self.children.is_none()
     }
+
+    /// Box covered by the `Octant`.
+    pub fn bounds(&self) -> Aabb {
+        axes_bounds(self.x_axis, self.y_axis, self.z_axis)
+    }
+}
+
+fn axes_bounds(x_axis: OctAxis, y_axis: OctAxis, z_axis: OctAxis) -> Aabb {
+    Aabb::new(
+        Point::new(x_axis.lower, y_axis.lower, z_axis.lower),
+        Point::new(x_axis.upper, y_axis.upper, z_axis.upper),
+    )
 }
 
 /// Stores a 3d representation of the shape functions at arbitrary resolutions.
+///
+/// Every shape is rendered into a subtree of its own covering the whole range of the octree,
+/// identified by its `ShapeHandle`. Subtrees never share octants, so a leaf only ever holds the
+/// surface of the one shape it records in `Octant::shape`, and a shape can be extracted,
+/// replaced or removed without touching the others. Where shapes overlap their meshes cross
+/// each other instead of merging, shapes meant to be one solid should be combined (with
+/// `union`) into a single `CsgFunc` first.
+///
+/// Octants are only added or removed along with a shape, which needs `&mut Octree`. A shared
+/// `&Octree` is therefore a frozen view of the finished tree, handing out `&Octant`s without
+/// copying or locking.
 #[derive(Debug)]
 pub struct Octree {
     // Stores all octants in the octree, child/parent relationships are maintained
     // in the Octant itself.
-    octants: Mutex<Vec<Octant>>,
+    octants: Vec<Octant>,
     range: OctAxis,
-    root_idx: Option<OctantIdx>,
+    // Subtree of every shape by `ShapeHandle`, `None` once removed.
+    shapes: Vec<Option<RenderedShape>>,
+    // Maximum QEF error allowed when collapsing a subtree into a single leaf.
+    tolerance: f32,
+    progress: Progress,
+}
+
+/// Root of the subtree of a shape, along with how it was built.
+#[derive(Debug, Clone, Copy)]
+struct RenderedShape {
+    root: OctantIdx,
+    depth: u8,
+    tolerance: f32,
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Subtree of the previous render of a shape covering the same axes as the octant being built,
+/// copied over as long as the octant doesn't overlap `changed`.
+#[derive(Debug, Clone, Copy)]
+struct Reuse<'a> {
+    changed: &'a Aabb,
+    idx: OctantIdx,
+}
+
+#[derive(Debug)]
 enum Subdivided {
-    Value(f32),
+    /// Leaf octant that is not in the octree yet, it can still be merged with its siblings.
+    Leaf(Box<Octant>),
     Idx(OctantIdx),
 }
 
@@ -93,86 +240,386 @@ impl Octree {
     /// Creates a new octree bounding the space of `bounds` in 3 dimensions.
     pub fn new(lower_bound: f32, upper_bound: f32) -> Self {
         Octree {
-            octants: Mutex::new(vec![]),
+            octants: vec![],
             range: OctAxis::new(lower_bound, upper_bound),
-            root_idx: None,
+            shapes: vec![],
+            tolerance: 0.0,
+            progress: Progress::new(),
         }
     }
 
+    /// Sets the maximum QEF error allowed when simplifying the octree.
+    ///
+    /// Subtrees whose combined feature stays under `tolerance` are collapsed into a single leaf.
+    /// A tolerance of `0.0` (the default) disables simplification.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance;
+    }
+
+    /// Sets the progress renders and face extraction report to, and which can cancel them.
+    ///
+    /// A cancelled render stops early and leaves an incomplete shape behind, which should be
+    /// thrown away along with the octree.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
     /// Adds an object to the Octree rendered from the `function` at a resolution of `resolution`
     pub fn render_shape(&mut self, resolution: f32, function: &CsgFunc) -> ShapeHandle {
-        let depth = (self.range.length() / resolution).log2() as u8;
-        if let Subdivided::Idx(root_idx) =
-            self.subdivide(self.range, self.range, self.range, depth, function)
-        {
-            self.root_idx = Some(root_idx);
+        let handle = self.shapes.len();
+        let shape = self.build_shape(handle, resolution, function, None);
+        self.shapes.push(Some(shape));
+        handle
+    }
+
+    /// Renders `function` in place of the shape `handle`, which keeps its handle.
+    ///
+    /// Returns false, leaving the octree untouched, if there is no such shape.
+    pub fn replace_shape(
+        &mut self,
+        handle: ShapeHandle,
+        resolution: f32,
+        function: &CsgFunc,
+    ) -> bool {
+        if self.shape_root(handle).is_none() {
+            return false;
+        }
+        let shape = self.build_shape(handle, resolution, function, None);
+        self.shapes[handle] = Some(shape);
+        self.compact();
+        true
+    }
+
+    /// Renders `function` in place of the shape `handle` like `replace_shape`, but only the
+    /// octants overlapping `changed` are built again. The others are copied from the previous
+    /// render, so `changed` has to cover everywhere the shape crosses its surface differently
+    /// (see `shape::changed_region`).
+    ///
+    /// Returns the region in which the leaves of the shape were replaced, faces built from cells
+    /// outside of it stay the same. Everything is built again if the resolution or tolerance
+    /// changed since the last render. `None`, leaving the octree untouched, if there is no such
+    /// shape.
+    pub fn update_shape(
+        &mut self,
+        handle: ShapeHandle,
+        resolution: f32,
+        function: &CsgFunc,
+        changed: &Aabb,
+    ) -> Option<Aabb> {
+        let previous = self.shapes.get(handle).copied().flatten()?;
+        if previous.depth != self.depth(resolution) || previous.tolerance != self.tolerance {
+            self.replace_shape(handle, resolution, function);
+            return Some(axes_bounds(self.range, self.range, self.range));
+        }
+
+        // Normals are sampled a little way around the surface, keep the copied octants clear of
+        // the change
+        let changed = changed.expand(resolution * 2.0);
+        let reuse = Reuse { changed: &changed, idx: previous.root };
+        let replaced = self.leaves_overlapping(previous.root, &changed);
+        let shape = self.build_shape(handle, resolution, function, Some(reuse));
+        let built = self.leaves_overlapping(shape.root, &changed);
+        self.shapes[handle] = Some(shape);
+        self.compact();
+
+        // Leaves built again lie within a new or previous leaf overlapping the change
+        match (replaced, built) {
+            (Some(replaced), Some(built)) => Some(replaced.union(&built)),
+            (replaced, built) => replaced.or(built).or(Some(changed)),
+        }
+    }
+
+    /// Removes the shape `handle` and its octants from the octree.
+    ///
+    /// Returns false if there is no such shape.
+    pub fn remove_shape(&mut self, handle: ShapeHandle) -> bool {
+        match self.shapes.get_mut(handle).and_then(Option::take) {
+            Some(_) => {
+                self.compact();
+                true
+            }
+            None => false,
         }
-        warn!("Rendering a shape, ShapeHandle not yet implemented");
-        0
     }
 
+    /// Handles of every shape in the octree, in the order they were added.
+    pub fn handles(&self) -> impl Iterator<Item = ShapeHandle> + '_ {
+        self.shapes.iter().enumerate().filter_map(|(handle, shape)| shape.map(|_| handle))
+    }
+
+    /// Index of the root octant of the shape `handle`, `None` if there is no such shape.
+    pub fn shape_root(&self, handle: ShapeHandle) -> Option<OctantIdx> {
+        self.shapes.get(handle).copied().flatten().map(|shape| shape.root)
+    }
+
+    /// Extracts the faces of every shape with dual contouring.
+    ///
+    /// See `mesher` for the other ways of turning the octree into faces.
     pub fn extract_faces(&self) -> Vec<Face> {
-        match self.root_idx {
-            Some(idx) => dual_contour::cell_proc(self, idx),
+        self.handles().flat_map(|handle| self.extract_shape_faces(handle)).collect()
+    }
+
+    /// Extracts the faces of the shape `handle` with dual contouring.
+    pub fn extract_shape_faces(&self, handle: ShapeHandle) -> Vec<Face> {
+        match self.shape_root(handle) {
+            Some(idx) => dual_contour::cell_proc(self, idx, |feature| feature.point),
             None => vec![],
         }
     }
 
-    /// Adds an Octant to the Octree returning an `OctantIdx` to represent it's place in the tree.
-    fn add_octant(&self, oct: Octant) -> OctantIdx {
-        let mut octants = self.octants.lock();
-        octants.push(oct);
-        octants.len() - 1
+    /// Returns a refrence to an `Octant` from an `OctantIdx`
+    pub fn get_octant(&self, idx: OctantIdx) -> &Octant {
+        &self.octants[idx]
     }
 
-    /// Returns a refrence to an `Octant` from an `OctantIdx`
-    pub fn get_octant(&self, idx: OctantIdx) -> Octant {
-        let octants = self.octants.lock();
-        octants[idx].clone()
+    /// Iterates over the leaf octants of every shape without copying them.
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves {
+            octants: &self.octants,
+            queue: self.shapes.iter().rev().flatten().map(|shape| shape.root).collect(),
+        }
+    }
+
+    /// Iterates over the leaf octants of the shape `handle` without copying them.
+    pub fn shape_leaves(&self, handle: ShapeHandle) -> Leaves<'_> {
+        Leaves { octants: &self.octants, queue: self.shape_root(handle).into_iter().collect() }
+    }
+
+    /// Depth of the subtrees rendered at `resolution`.
+    fn depth(&self, resolution: f32) -> u8 {
+        (self.range.length() / resolution).log2() as u8
+    }
+
+    /// Box around the leaves of the subtree `idx` that overlap `region`, `None` if there are none.
+    fn leaves_overlapping(&self, idx: OctantIdx, region: &Aabb) -> Option<Aabb> {
+        let octant = &self.octants[idx];
+        if !octant.bounds().overlaps(region) {
+            return None;
+        }
+        match octant.children {
+            Some(children) => children
+                .iter()
+                .filter_map(|&child| self.leaves_overlapping(child, region))
+                .reduce(|a, b| a.union(&b)),
+            None => Some(octant.bounds()),
+        }
+    }
+
+    /// Builds the subtree of a shape into the octree, copying the octants of `reuse` outside of
+    /// the region that changed.
+    fn build_shape(
+        &mut self,
+        handle: ShapeHandle,
+        resolution: f32,
+        function: &CsgFunc,
+        reuse: Option<Reuse>,
+    ) -> RenderedShape {
+        self.progress.start(Stage::Rendering);
+        let depth = self.depth(resolution);
+        let corners = dual_contour::sample_corners(self.range, self.range, self.range, function);
+        let mut arena = Arena::default();
+        let (range, tolerance) = (self.range, self.tolerance);
+        let root = {
+            // Counts the visits of the subtree built on this thread
+            let mut tally = self.progress.tally();
+            self.subdivide(
+                &mut arena, &mut tally, range, range, range, corners, depth, function, reuse,
+            )
+        };
+        let root = add_subdivided(&mut arena, root);
+
+        let offset = self.octants.len();
+        self.octants.reserve(arena.len);
+        arena.flatten(&mut self.octants);
+        // Octants are built without knowing their shape, every one of them is new here
+        for octant in &mut self.octants[offset..] {
+            octant.shape = handle;
+        }
+        RenderedShape { root: offset + root, depth, tolerance }
+    }
+
+    /// Copies the subtree `idx` into `arena`, leaves are returned without being added like in
+    /// `subdivide`.
+    fn copy_subtree(&self, arena: &mut Arena, idx: OctantIdx) -> Subdivided {
+        let octant = &self.octants[idx];
+        match octant.children {
+            Some(children) => {
+                let children = children.map(|child| {
+                    let copy = self.copy_subtree(arena, child);
+                    add_subdivided(arena, copy)
+                });
+                let mut copy = octant.clone();
+                copy.children = Some(children);
+                Subdivided::Idx(arena.push(copy))
+            }
+            None => Subdivided::Leaf(Box::new(octant.clone())),
+        }
+    }
+
+    /// Drops the octants no shape refers to anymore, keeping the order of the others.
+    fn compact(&mut self) {
+        let mut live = vec![false; self.octants.len()];
+        let mut queue: Vec<OctantIdx> =
+            self.shapes.iter().flatten().map(|shape| shape.root).collect();
+        while let Some(idx) = queue.pop() {
+            live[idx] = true;
+            if let Some(children) = self.octants[idx].children {
+                queue.extend_from_slice(&children);
+            }
+        }
+
+        let mut remap = vec![0; self.octants.len()];
+        let kept = live.iter().enumerate().filter(|(_, &live)| live);
+        for (new_idx, (idx, _)) in kept.enumerate() {
+            remap[idx] = new_idx;
+        }
+
+        let octants = std::mem::take(&mut self.octants);
+        self.octants = octants
+            .into_iter()
+            .zip(live)
+            .filter(|(_, live)| *live)
+            .map(|(mut octant, _)| {
+                if let Some(children) = &mut octant.children {
+                    children.iter_mut().for_each(|child| *child = remap[*child]);
+                }
+                octant
+            })
+            .collect();
+        for shape in self.shapes.iter_mut().flatten() {
+            shape.root = remap[shape.root];
+        }
     }
 
     /// Checks if the `Subdivided` regions can be merged into a single octant.
     /// Examples: all the subregions are contained within the shape.
     ///
-    /// TODO: add some fancier logic, bilinear interpolation perhaps?
-    fn merge_octants(octant_children: &Vec<Subdivided>) -> Option<Subdivided> {
-        // If all the values are within the shape, unify the region
-        if octant_children.iter().all(|&x| match x {
-            Subdivided::Value(x) => x < 0.0,
-            _ => false,
-        }) {
-            return Some(octant_children[0]);
+    /// `lattice` holds the values at the corners of every child.
+    fn merge_octants(
+        x_axis: OctAxis,
+        y_axis: OctAxis,
+        z_axis: OctAxis,
+        corners: [f32; 8],
+        lattice: &[f32; 27],
+        octant_children: &[Subdivided],
+    ) -> Option<Subdivided> {
+        let leaves = octant_children.iter().all(|child| match child {
+            Subdivided::Leaf(octant) => !octant.has_feature(),
+            Subdivided::Idx(_) => false,
+        });
+
+        // If all the corners are within the shape, or all outside of it, unify the region
+        let uniform = lattice.iter().all(|&v| v < 0.0) || lattice.iter().all(|&v| v >= 0.0);
+        if leaves && uniform {
+            return Some(Subdivided::Leaf(Box::new(Octant::new(x_axis, y_axis, z_axis, corners))));
+        }
+
+        None
+    }
+
+    /// Checks if the `Subdivided` regions can be collapsed into a single leaf octant.
+    ///
+    /// Every child must be a leaf with at most one sheet of surface, the QEF accumulated from the
+    /// children must be solvable within the tolerance of the octree and the collapse must not
+    /// change the topology. Only the data stored in the children is used, the shape is not
+    /// sampled again.
+    fn collapse_octants(
+        &self,
+        x_axis: OctAxis,
+        y_axis: OctAxis,
+        z_axis: OctAxis,
+        corners: [f32; 8],
+        lattice: &[f32; 27],
+        octant_children: &[Subdivided],
+    ) -> Option<Octant> {
+        let leaves: Vec<&Octant> = octant_children
+            .iter()
+            .map(|child| match child {
+                Subdivided::Leaf(octant) => Some(octant.as_ref()),
+                Subdivided::Idx(_) => None,
+            })
+            .collect::<Option<_>>()?;
+
+        let mut qef = Qef::new();
+        for leaf in &leaves {
+            match leaf.features[..] {
+                [] => {}
+                [ref feature] => qef.merge(&feature.qef),
+                _ => return None,
+            }
         }
 
-        // If all the values are outside the shape, unify the region
-        if octant_children.iter().all(|&x| match x {
-            Subdivided::Value(x) => x >= 0.0,
-            _ => false,
-        }) {
-            return Some(octant_children[0]);
+        let (point, error) = dual_contour::feature_from_qef(&qef, x_axis, y_axis, z_axis)?;
+        if error > self.tolerance {
+            return None;
         }
 
-        None
+        // The collapsed octant must still be crossed by a single sheet of surface
+        let mut octant = Octant::new(x_axis, y_axis, z_axis, corners);
+        let edges = match dual_contour::surface_components(octant.inside())[..] {
+            [edges] => edges,
+            _ => return None,
+        };
+
+        if !dual_contour::topology_safe(lattice) {
+            return None;
+        }
+
+        // Each edge is split in two by the children, at most one half crosses the surface
+        for (edge, &(c0, c1)) in dual_contour::EDGES.iter().enumerate() {
+            if edges & (1 << edge) != 0 {
+                octant.hermite[edge] = leaves[c0].hermite[edge].or(leaves[c1].hermite[edge]);
+            }
+        }
+        octant.features = smallvec::smallvec![Feature { point, edges, qef }];
+        Some(octant)
     }
 
+    /// Builds the subtree covering the given axes, adding its octants to `arena`.
+    ///
+    /// Leaves are returned without being added so they can still be merged by the caller.
+    /// Visited octants are counted in `tally`, subtrees built on other threads count in tallies
+    /// of their own.
+    #[allow(clippy::too_many_arguments)]
     fn subdivide(
         &self,
+        arena: &mut Arena,
+        tally: &mut Tally,
         x_axis: OctAxis,
         y_axis: OctAxis,
         z_axis: OctAxis,
+        corners: [f32; 8],
         depth: u8,
         shape_func: &CsgFunc,
+        reuse: Option<Reuse>,
     ) -> Subdivided {
+        // Part of the whole octree finished once this octant has no more children to build
+        let completed = (x_axis.length() / self.range.length()).powi(3) as f64;
+        if self.progress.is_cancelled() {
+            tally.visit(completed);
+            return Subdivided::Leaf(Box::new(Octant::new(x_axis, y_axis, z_axis, corners)));
+        }
+
+        if let Some(reuse) = reuse {
+            if !axes_bounds(x_axis, y_axis, z_axis).overlaps(reuse.changed) {
+                tally.visit(completed);
+                return self.copy_subtree(arena, reuse.idx);
+            }
+        }
+
         if depth == 0 {
+            tally.visit(completed);
             // We're at the bottom of the octree, generate a leaf node Octant
-            return Subdivided::Value(shape_func.call(
-                x_axis.center(),
-                y_axis.center(),
-                z_axis.center(),
-            ));
+            let octant = Octant::leaf(x_axis, y_axis, z_axis, corners, shape_func);
+            return Subdivided::Leaf(Box::new(octant));
         }
 
         // Since not at the leaf node, check every child octant in the current octant
+        tally.visit(0.0);
         let (left_x, right_x) = x_axis.split();
         let (bottom_y, top_y) = y_axis.split();
         let (front_z, back_z) = z_axis.split();
@@ -189,44 +636,183 @@ impl Octree {
             [right_x, bottom_y, back_z],
         ];
 
+        // Sample the corners of every child, reusing the corners of this octant
+        let lattice = dual_contour::sample_lattice(x_axis, y_axis, z_axis, corners, shape_func);
+        // Children of a previous leaf are built from scratch
+        let previous_children =
+            reuse.and_then(|reuse| Some((reuse.changed, self.octants[reuse.idx].children?)));
+        let subdivide_child =
+            |arena: &mut Arena, tally: &mut Tally, (child, [x, y, z]): (usize, &[OctAxis; 3])| {
+                let child_corners = dual_contour::child_corners(&lattice, child);
+                let reuse = previous_children
+                    .map(|(changed, children)| Reuse { changed, idx: children[child] });
+                self.subdivide(
+                    arena,
+                    tally,
+                    *x,
+                    *y,
+                    *z,
+                    child_corners,
+                    new_depth,
+                    shape_func,
+                    reuse,
+                )
+            };
+
+        // Large subtrees are built in parallel into arenas of their own, then appended to ours.
         #[cfg(feature = "parallel")]
-        let octant_children = subdivides
-            .par_iter()
-            .map(|[x, y, z]| self.subdivide(*x, *y, *z, new_depth, shape_func));
+        let octant_children: Vec<Subdivided> = if depth >= PARALLEL_DEPTH {
+            let subtrees: Vec<(Arena, Subdivided)> = subdivides
+                .par_iter()
+                .enumerate()
+                .map(|child| {
+                    let (mut subtree, mut tally) = (Arena::default(), self.progress.tally());
+                    let subdivided = subdivide_child(&mut subtree, &mut tally, child);
+                    (subtree, subdivided)
+                })
+                .collect();
+            subtrees
+                .into_iter()
+                .map(|(subtree, subdivided)| match subdivided {
+                    Subdivided::Idx(idx) => Subdivided::Idx(arena.append(subtree) + idx),
+                    // Leaves are not added to the arena, so the subtree is empty
+                    leaf => leaf,
+                })
+                .collect()
+        } else {
+            subdivides
+                .iter()
+                .enumerate()
+                .map(|child| subdivide_child(arena, tally, child))
+                .collect()
+        };
 
         #[cfg(not(feature = "parallel"))]
-        let octant_children =
-            subdivides.iter().map(|[x, y, z]| self.subdivide(*x, *y, *z, new_depth, shape_func));
+        let octant_children: Vec<Subdivided> = subdivides
+            .iter()
+            .enumerate()
+            .map(|child| subdivide_child(arena, tally, child))
+            .collect();
 
-        let octant_children = octant_children.collect();
         // Merge octants if possible
-        if let Some(merged_region) = Self::merge_octants(&octant_children) {
+        if let Some(merged_region) =
+            Self::merge_octants(x_axis, y_axis, z_axis, corners, &lattice, &octant_children)
+        {
             return merged_region;
         }
 
+        // Simplify the octree by collapsing the children if the error is acceptable
+        if self.tolerance > 0.0 {
+            if let Some(octant) =
+                self.collapse_octants(x_axis, y_axis, z_axis, corners, &lattice, &octant_children)
+            {
+                return Subdivided::Leaf(Box::new(octant));
+            }
+        }
+
+        // If the subdivide region already exists, use it's index, otherwise add the leaf octant.
+        let octant_children: Vec<OctantIdx> =
+            octant_children.into_iter().map(|child| add_subdivided(arena, child)).collect();
+
         // Create the new octant and connect it's children below
-        let root = self.add_octant(Octant::new(x_axis, y_axis, z_axis, None));
-
-        // If the subdivide region already exists, return it's index,
-        // otherwise create a new octant.
-        let octant_children =
-            octant_children.iter().zip(subdivides).map(|(child, [x, y, z])| match child {
-                Subdivided::Idx(idx) => *idx,
-                Subdivided::Value(_) => {
-                    let feature = dual_contour::new_feature(x, y, z, shape_func);
-                    self.add_octant(Octant::new(x, y, z, feature))
+        let mut octant = Octant::new(x_axis, y_axis, z_axis, corners);
+        octant.children = Some(octant_children.try_into().unwrap());
+
+        Subdivided::Idx(arena.push(octant))
+    }
+}
+
+/// Adds the `Subdivided` region to `arena` if it is a leaf, returning its index.
+fn add_subdivided(arena: &mut Arena, subdivided: Subdivided) -> OctantIdx {
+    match subdivided {
+        Subdivided::Idx(idx) => idx,
+        Subdivided::Leaf(octant) => arena.push(*octant),
+    }
+}
+
+/// Append-only storage the octree is built into.
+///
+/// Subtrees built in parallel fill arenas of their own, which are joined to the arena of their
+/// parent without moving any octants. Indices are relative to the start of the arena, octants
+/// are moved once at the end when `flatten` lays every arena out one after another.
+#[derive(Debug, Default)]
+struct Arena {
+    segments: Vec<Segment>,
+    len: usize,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Octants(Vec<Octant>),
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    Subtree(Arena),
+}
+
+impl Arena {
+    /// Adds an Octant to the arena returning an `OctantIdx` to represent it's place in the tree.
+    fn push(&mut self, octant: Octant) -> OctantIdx {
+        match self.segments.last_mut() {
+            Some(Segment::Octants(octants)) => octants.push(octant),
+            _ => self.segments.push(Segment::Octants(vec![octant])),
+        }
+        self.len += 1;
+        self.len - 1
+    }
+
+    /// Joins the arena of a subtree, returning the offset of its indices within this arena.
+    #[cfg(feature = "parallel")]
+    fn append(&mut self, subtree: Arena) -> usize {
+        let offset = self.len;
+        self.len += subtree.len;
+        self.segments.push(Segment::Subtree(subtree));
+        offset
+    }
+
+    /// Lays out the octants of every arena one after another at the end of `octants`, shifting
+    /// their indices accordingly.
+    fn flatten(self, octants: &mut Vec<Octant>) {
+        let offset = octants.len();
+        for segment in self.segments {
+            match segment {
+                Segment::Octants(segment) => {
+                    octants.extend(segment.into_iter().map(|mut octant| {
+                        if let Some(children) = &mut octant.children {
+                            children.iter_mut().for_each(|child| *child += offset);
+                        }
+                        octant
+                    }))
                 }
-            });
+                Segment::Subtree(subtree) => subtree.flatten(octants),
+            }
+        }
+    }
+}
 
-        // keep this outside the lock, it collects os the children which also need the lock
-        let octant_children: Vec<OctantIdx> = octant_children.collect();
-        let octant_children = octant_children.try_into().unwrap();
-        {
-            let mut octants = self.octants.lock();
-            octants[root].children = Some(octant_children);
+/// Borrowing iterator over the leaves of an `Octree`, see `Octree::leaves`.
+pub struct Leaves<'a> {
+    octants: &'a [Octant],
+    queue: Vec<OctantIdx>,
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = &'a Octant;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let octant = &self.octants[self.queue.pop()?];
+            match octant.children {
+                Some(children) => self.queue.extend_from_slice(&children),
+                None => return Some(octant),
+            }
         }
+    }
+}
 
-        Subdivided::Idx(root)
+impl<'a> IntoIterator for &'a Octree {
+    type Item = &'a Octant;
+    type IntoIter = Leaves<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.leaves()
     }
 }
 
@@ -260,14 +846,7 @@ impl IntoIterator for Octree {
     type IntoIter = OctreeIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        match self.root_idx {
-            Some(root_idx) => {
-                OctreeIter { nodes: self.octants.lock().clone(), queue: vec![root_idx] }
-            }
-            None => {
-                eprintln!("Octree has no root index!");
-                OctreeIter { nodes: vec![], queue: vec![] }
-            }
-        }
+        let queue = self.shapes.iter().rev().flatten().map(|shape| shape.root).collect();
+        OctreeIter { nodes: self.octants, queue }
     }
 }
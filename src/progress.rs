@@ -0,0 +1,145 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Fixed point scale the completed part of the work is counted in.
+const SCALE: f64 = (1u64 << 52) as f64;
+
+/// What a render is busy with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Idle,
+    /// Subdividing the octree.
+    Rendering,
+    /// Extracting faces from the octree.
+    Extracting,
+}
+
+/// Shared between a long render and whoever waits on it, reporting how far along it is and
+/// letting it be cancelled.
+///
+/// Clones refer to the same progress. Work is measured by the part of the volume of the octree
+/// that is done, which the octants visited so far add up to.
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Arc<State>);
+
+#[derive(Debug, Default)]
+struct State {
+    stage: AtomicU8,
+    visited: AtomicUsize,
+    completed: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress::default()
+    }
+
+    /// Starts counting `stage` from zero.
+    pub fn start(&self, stage: Stage) {
+        self.0.visited.store(0, Ordering::Relaxed);
+        self.0.completed.store(0, Ordering::Relaxed);
+        self.0.stage.store(stage as u8, Ordering::Relaxed);
+    }
+
+    /// Goes back to idle once all the work is done.
+    pub fn finish(&self) {
+        self.start(Stage::Idle);
+    }
+
+    /// Goes back to idle and clears a cancel, ready for the next piece of work.
+    pub fn reset(&self) {
+        self.finish();
+        self.0.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn stage(&self) -> Stage {
+        match self.0.stage.load(Ordering::Relaxed) {
+            s if s == Stage::Rendering as u8 => Stage::Rendering,
+            s if s == Stage::Extracting as u8 => Stage::Extracting,
+            _ => Stage::Idle,
+        }
+    }
+
+    /// Counts an octant as visited, finishing `completed` of the volume of the stage.
+    pub fn visit(&self, completed: f64) {
+        self.add(1, completed);
+    }
+
+    /// Counts visits locally, see `Tally`.
+    pub fn tally(&self) -> Tally<'_> {
+        Tally { progress: self, visited: 0, completed: 0.0 }
+    }
+
+    fn add(&self, visited: usize, completed: f64) {
+        self.0.visited.fetch_add(visited, Ordering::Relaxed);
+        if completed > 0.0 {
+            self.0.completed.fetch_add((completed * SCALE) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Octants visited in the current stage.
+    pub fn visited(&self) -> usize {
+        self.0.visited.load(Ordering::Relaxed)
+    }
+
+    /// Estimated part of the current stage that is done, from 0 to 1.
+    pub fn completion(&self) -> f32 {
+        (self.0.completed.load(Ordering::Relaxed) as f64 / SCALE).min(1.0) as f32
+    }
+
+    /// Asks the work to stop, it winds down as soon as it notices. Work that was cancelled is
+    /// incomplete and should be thrown away.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Visits to add to a `Progress` in one go.
+///
+/// Every thread working on a stage keeps a tally of its own, so the shared counters are only
+/// touched every `Tally::BATCH` visits instead of on every octant. The rest is added when the
+/// tally is dropped.
+#[derive(Debug)]
+pub struct Tally<'a> {
+    progress: &'a Progress,
+    visited: usize,
+    completed: f64,
+}
+
+impl Tally<'_> {
+    /// Visits counted before they are added to the progress.
+    pub const BATCH: usize = 1024;
+
+    /// Same as `Progress::visit`.
+    pub fn visit(&mut self, completed: f64) {
+        self.visited += 1;
+        self.completed += completed;
+        if self.visited >= Self::BATCH {
+            self.flush();
+        }
+    }
+
+    pub fn progress(&self) -> &Progress {
+        self.progress
+    }
+
+    /// Adds the visits counted so far to the progress.
+    pub fn flush(&mut self) {
+        self.progress.add(self.visited, self.completed);
+        self.visited = 0;
+        self.completed = 0.0;
+    }
+}
+
+impl Drop for Tally<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
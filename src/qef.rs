@@ -0,0 +1,100 @@
+use {
+    crate::types::Point,
+    nalgebra::{linalg::SVD, Matrix3, Vector3},
+};
+
+/// Singular values smaller than this fraction of the largest singular value are treated as zero.
+const SINGULAR_TRUNCATION: f32 = 0.1;
+
+/// Quadratic error function built from hermite data (surface points and their normals).
+///
+/// Only the normal equations are stored (`AᵀA`, `Aᵀb` and `bᵀb`), so QEFs of neighbouring cells
+/// can be summed together when the octree is simplified.
+#[derive(Debug, Clone, Copy)]
+pub struct Qef {
+    ata: Matrix3<f32>,
+    atb: Vector3<f32>,
+    btb: f32,
+    mass_point: Vector3<f32>,
+    count: usize,
+}
+
+impl Default for Qef {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Qef {
+    /// Creates an empty QEF.
+    pub fn new() -> Self {
+        Qef {
+            ata: Matrix3::zeros(),
+            atb: Vector3::zeros(),
+            btb: 0.0,
+            mass_point: Vector3::zeros(),
+            count: 0,
+        }
+    }
+
+    /// Adds the plane going through `point` with the normal `normal`.
+    pub fn add(&mut self, point: Point, normal: Vector3<f32>) {
+        let b = normal.dot(&point.as_vector());
+        self.ata += normal * normal.transpose();
+        self.atb += normal * b;
+        self.btb += b * b;
+        self.mass_point += point.as_vector();
+        self.count += 1;
+    }
+
+    /// Adds all the planes of `other` to this QEF.
+    pub fn merge(&mut self, other: &Qef) {
+        self.ata += other.ata;
+        self.atb += other.atb;
+        self.btb += other.btb;
+        self.mass_point += other.mass_point;
+        self.count += other.count;
+    }
+
+    /// Number of planes accumulated in the QEF.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Average of every point added to the QEF.
+    pub fn mass_point(&self) -> Point {
+        let mean = self.mass_point / self.count.max(1) as f32;
+        Point::new(mean.x, mean.y, mean.z)
+    }
+
+    /// Sum of the squared distances from `point` to every plane in the QEF.
+    pub fn error(&self, point: Point) -> f32 {
+        let x = point.as_vector();
+        let error = x.dot(&(self.ata * x)) - 2.0 * x.dot(&self.atb) + self.btb;
+        error.max(0.0)
+    }
+
+    /// Find the point that minimizes the error, returning it along with its residual error.
+    ///
+    /// The system is solved relative to the mass point, singular values of `AᵀA` smaller than
+    /// `SINGULAR_TRUNCATION` times the largest one are discarded. Directions the normals do not
+    /// constrain (flat surfaces, edges) therefore stay at the mass point instead of shooting off.
+    pub fn solve(&self) -> (Point, f32) {
+        let mass_point = self.mass_point().as_vector();
+        let atb = self.atb - self.ata * mass_point;
+
+        let svd = SVD::new(self.ata, true, true);
+        let eps = svd.singular_values.max() * SINGULAR_TRUNCATION;
+        let solution = match svd.solve(&atb, eps) {
+            Ok(offset) => mass_point + offset,
+            Err(_) => mass_point,
+        };
+        let point = Point::new(solution.x, solution.y, solution.z);
+
+        (point, self.error(point))
+    }
+}
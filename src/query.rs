@@ -0,0 +1,271 @@
+use {
+    crate::{
+        dual_contour,
+        octree::{OctAxis, Octant, OctantIdx, Octree, ShapeHandle},
+        shape::CsgFunc,
+        types::{Point, Triangle},
+    },
+    nalgebra::Vector3,
+    smallvec::SmallVec,
+};
+
+/// Steps taken by `raymarch` before giving up on reaching the surface.
+const MAX_STEPS: usize = 512;
+
+/// How close to the surface `raymarch` has to get to count as a hit.
+const HIT_DISTANCE: f32 = 1e-4;
+
+/// Half line starting at `origin`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point,
+    /// Unit length direction of the ray.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Point, direction: Vector3<f32>) -> Self {
+        Ray { origin, direction: direction.normalize() }
+    }
+
+    /// Point at `distance` along the ray.
+    pub fn at(&self, distance: f32) -> Point {
+        let at = self.origin.as_vector() + self.direction * distance;
+        Point::new(at.x, at.y, at.z)
+    }
+}
+
+/// Where a ray meets the surface of a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub point: Point,
+    /// Distance along the ray.
+    pub distance: f32,
+    /// Surface normal, pointing out of the shape.
+    pub normal: Vector3<f32>,
+}
+
+/// Finds the first surface crossed by `ray` within `max_distance` by sphere tracing.
+///
+/// The value of the shape is taken as a bound on the distance to the surface, so the ray can
+/// safely step that far. Rays starting within the shape find the surface they leave it through.
+/// Shapes overestimating the distance can step past the surface, when a step ends on the other
+/// side of it the crossing is found by bisecting that step.
+pub fn raymarch(csg_func: &CsgFunc, ray: &Ray, max_distance: f32) -> Option<Hit> {
+    let hit = |distance: f32| {
+        let point = ray.at(distance);
+        Hit { point, distance, normal: csg_func.normal(point.x, point.y, point.z) }
+    };
+
+    let mut distance = 0.0;
+    let mut value = csg_func.call_point(ray.origin);
+    let start_inside = value < 0.0;
+    for _ in 0..MAX_STEPS {
+        if value.abs() < HIT_DISTANCE {
+            return Some(hit(distance));
+        }
+        let next = distance + value.abs();
+        if next > max_distance {
+            return None;
+        }
+        let next_value = csg_func.call_point(ray.at(next));
+        if (next_value < 0.0) != start_inside {
+            // Stepped over the surface, it lies between the last two steps
+            let (mut before, mut after) = (distance, next);
+            while after - before > HIT_DISTANCE {
+                let middle = (before + after) / 2.0;
+                match (csg_func.call_point(ray.at(middle)) < 0.0) == start_inside {
+                    true => before = middle,
+                    false => after = middle,
+                }
+            }
+            return Some(hit(after));
+        }
+        distance = next;
+        value = next_value;
+    }
+    None
+}
+
+/// Approximate value of the shapes rendered into `tree` at `point`, interpolated from the
+/// corners of the leaves containing it. Shapes are combined like a union. `None` outside of the
+/// octree or when it holds no shapes.
+pub fn value(tree: &Octree, point: Point) -> Option<f32> {
+    tree.handles().filter_map(|handle| shape_value(tree, handle, point)).reduce(f32::min)
+}
+
+/// Approximate value of the shape `handle` at `point`, like `value`.
+pub fn shape_value(tree: &Octree, handle: ShapeHandle, point: Point) -> Option<f32> {
+    let leaf = leaf_at(tree, tree.shape_root(handle)?, point)?;
+    let t = |axis: OctAxis, value: f32| (value - axis.lower) / axis.length();
+    let (tx, ty, tz) = (t(leaf.x_axis, point.x), t(leaf.y_axis, point.y), t(leaf.z_axis, point.z));
+    let value = (0..8)
+        .map(|corner| {
+            let wx = if corner & 4 != 0 { tx } else { 1.0 - tx };
+            let wy = if corner & 2 != 0 { 1.0 - ty } else { ty };
+            let wz = if corner & 1 != 0 { tz } else { 1.0 - tz };
+            leaf.corners[corner] * wx * wy * wz
+        })
+        .sum();
+    Some(value)
+}
+
+/// Returns a bool based on if `point` lies within any shape rendered into `tree`, `None` outside
+/// of the octree.
+pub fn inside(tree: &Octree, point: Point) -> Option<bool> {
+    value(tree, point).map(|value| value < 0.0)
+}
+
+/// Closest point to `point` on the surface of any shape rendered into `tree`.
+///
+/// The surface of every leaf is made out of triangles fanning out from its feature points to
+/// its surface crossings, keeping sharp features. Subtrees further away than the closest point
+/// found so far are skipped. `None` when there is no surface at all.
+pub fn nearest_point(tree: &Octree, point: Point) -> Option<Point> {
+    let mut nearest = None;
+    for handle in tree.handles() {
+        nearest_in(tree, tree.shape_root(handle)?, point.as_vector(), &mut nearest);
+    }
+    nearest.map(|(_, at)| at)
+}
+
+/// Distance from `point` to the surface of the shapes rendered into `tree`, negative within
+/// them.
+pub fn distance(tree: &Octree, point: Point) -> Option<f32> {
+    let distance = (nearest_point(tree, point)? - point).as_vector().norm();
+    match inside(tree, point) {
+        Some(true) => Some(-distance),
+        _ => Some(distance),
+    }
+}
+
+/// Descends from `root` to the leaf containing `point`.
+fn leaf_at(tree: &Octree, root: OctantIdx, point: Point) -> Option<&Octant> {
+    let mut octant = tree.get_octant(root);
+    if !(octant.x_axis.contains(point.x)
+        && octant.y_axis.contains(point.y)
+        && octant.z_axis.contains(point.z))
+    {
+        return None;
+    }
+    while let Some(children) = octant.children {
+        let child = (point.x >= octant.x_axis.center()) as usize * 4
+            + (point.y < octant.y_axis.center()) as usize * 2
+            + (point.z >= octant.z_axis.center()) as usize;
+        octant = tree.get_octant(children[child]);
+    }
+    Some(octant)
+}
+
+/// Updates `nearest` (squared distance and point) with the surface of the subtree `idx`.
+fn nearest_in(
+    tree: &Octree,
+    idx: OctantIdx,
+    point: Vector3<f32>,
+    nearest: &mut Option<(f32, Point)>,
+) {
+    let octant = tree.get_octant(idx);
+    if let Some((best, _)) = *nearest {
+        if box_distance_squared(octant, point) >= best {
+            return;
+        }
+    }
+
+    match octant.children {
+        Some(children) => {
+            let mut children: SmallVec<[(f32, OctantIdx); 8]> = children
+                .iter()
+                .map(|&child| (box_distance_squared(tree.get_octant(child), point), child))
+                .collect();
+            children.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+            for (_, child) in children {
+                nearest_in(tree, child, point, nearest);
+            }
+        }
+        None => {
+            for triangle in leaf_surface(octant) {
+                let closest = closest_on_triangle(&triangle, point);
+                let squared = (closest - point).norm_squared();
+                let closer = match *nearest {
+                    Some((best, _)) => squared < best,
+                    None => true,
+                };
+                if closer {
+                    *nearest = Some((squared, Point::new(closest.x, closest.y, closest.z)));
+                }
+            }
+        }
+    }
+}
+
+/// Squared distance from `point` to the box of the octant, 0 within it.
+fn box_distance_squared(octant: &Octant, point: Vector3<f32>) -> f32 {
+    let outside = |axis: OctAxis, value: f32| value - axis.clamp(value);
+    Vector3::new(
+        outside(octant.x_axis, point.x),
+        outside(octant.y_axis, point.y),
+        outside(octant.z_axis, point.z),
+    )
+    .norm_squared()
+}
+
+/// Triangles approximating the surface within a leaf.
+fn leaf_surface(octant: &Octant) -> SmallVec<[Triangle; 12]> {
+    let inside = octant.inside();
+    let mut triangles = SmallVec::new();
+    for feature in &octant.features {
+        let crossings: SmallVec<[Point; 12]> = dual_contour::edge_loop(inside, feature.edges)
+            .into_iter()
+            .filter_map(|edge| octant.hermite[edge].map(|hermite| hermite.point))
+            .collect();
+        for (i, &crossing) in crossings.iter().enumerate() {
+            let next = crossings[(i + 1) % crossings.len()];
+            triangles.push([feature.point, crossing, next]);
+        }
+    }
+    triangles
+}
+
+/// Closest point to `point` on a triangle (see Ericson, "Real-Time Collision Detection" 5.1.5).
+fn closest_on_triangle(triangle: &Triangle, point: Vector3<f32>) -> Vector3<f32> {
+    let [a, b, c] = triangle.map(|p| p.as_vector());
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d1, d2) = (ab.dot(&ap), ac.dot(&ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let (d3, d4) = (ab.dot(&bp), ac.dot(&bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let (d5, d6) = (ab.dot(&cp), ac.dot(&cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Within the triangle, degenerate triangles end up at a corner above
+    let denominator = va + vb + vc;
+    if denominator.abs() <= f32::EPSILON {
+        return a;
+    }
+    a + ab * (vb / denominator) + ac * (vc / denominator)
+}
@@ -0,0 +1,91 @@
+use crate::{
+    mesher::{self, Mesher, MesherKind},
+    octree::{Octree, ShapeHandle},
+    shape::{self, Change, CsgFunc},
+    types::{Aabb, Face, Triangle},
+};
+
+/// Keeps the octree and faces of a shape up to date while the shape is being edited.
+///
+/// Every new version of the shape is diffed against the previous one (see
+/// `shape::changed_region`). Only the octants overlapping the part that changed are built again,
+/// and only the faces around them are extracted again.
+#[derive(Debug)]
+pub struct Remesher {
+    octree: Octree,
+    handle: ShapeHandle,
+    resolution: f32,
+    mesher: MesherKind,
+    csg_func: CsgFunc,
+    // Faces along with the box around the cells they were built from
+    faces: Vec<(Face, Aabb)>,
+}
+
+impl Remesher {
+    /// Renders `csg_func` into `octree` at a resolution of `resolution`.
+    pub fn new(mut octree: Octree, resolution: f32, mesher: MesherKind, csg_func: CsgFunc) -> Self {
+        let handle = octree.render_shape(resolution, &csg_func);
+        let mut remesher = Remesher { octree, handle, resolution, mesher, csg_func, faces: vec![] };
+        remesher.extract_all();
+        remesher
+    }
+
+    /// Replaces the shape with a new version of it, returning what changed.
+    pub fn update(&mut self, csg_func: CsgFunc) -> Change {
+        let change = shape::changed_region(&self.csg_func, &csg_func);
+        match change {
+            Change::Unchanged => {}
+            Change::Region(region) => {
+                let rebuilt = self
+                    .octree
+                    .update_shape(self.handle, self.resolution, &csg_func, &region)
+                    .expect("the shape of a remesher is never removed");
+                // Faces built from cells clear of the rebuilt leaves are the same as before
+                self.faces.retain(|(_, cells)| !cells.overlaps(&rebuilt));
+                let faces = self.mesher.extract_shape_faces_in(&self.octree, self.handle, &rebuilt);
+                self.faces.extend(faces);
+            }
+            Change::Everything => {
+                self.octree.replace_shape(self.handle, self.resolution, &csg_func);
+                self.extract_all();
+            }
+        }
+        self.csg_func = csg_func;
+        change
+    }
+
+    /// Renders the whole shape again at a resolution of `resolution`.
+    pub fn set_resolution(&mut self, resolution: f32) {
+        self.resolution = resolution;
+        self.octree.replace_shape(self.handle, resolution, &self.csg_func);
+        self.extract_all();
+    }
+
+    pub fn octree(&self) -> &Octree {
+        &self.octree
+    }
+
+    pub fn csg_func(&self) -> &CsgFunc {
+        &self.csg_func
+    }
+
+    /// Gives the shape back, dropping the octree and faces.
+    pub fn into_csg_func(self) -> CsgFunc {
+        self.csg_func
+    }
+
+    pub fn faces(&self) -> impl Iterator<Item = &Face> {
+        self.faces.iter().map(|(face, _)| face)
+    }
+
+    /// Splits the faces into triangles, see `mesher::triangulate`.
+    pub fn triangles(&self) -> Vec<Triangle> {
+        mesher::triangulate(self.faces(), &self.csg_func)
+    }
+
+    fn extract_all(&mut self) {
+        let root = self.octree.shape_root(self.handle).expect("the shape is never removed");
+        let everything = self.octree.get_octant(root).bounds();
+        self.faces = self.mesher.extract_shape_faces_in(&self.octree, self.handle, &everything);
+    }
+}
@@ -4,14 +4,13 @@ use {
         model::{
             self,
             faces::{DrawFaces, FaceMesh},
-            octants::{DrawOctant, OctantMesh},
+            octants::{DrawOctant, OctantMesh, Outline},
+            overlay::{DrawOverlay, OverlayMesh, Rectangle},
             points::{DrawPoints, PointMesh},
-            Vertex,
+            text, Vertex,
         },
-        octree::Octant,
         texture,
-        types::{Face, Point},
-        CsgFunc,
+        types::{Point, Triangle},
     },
     log::warn,
     wgpu::util::DeviceExt,
@@ -28,6 +27,8 @@ pub struct RenderState {
     config: wgpu::SurfaceConfiguration,
 
     render_pipeline: wgpu::RenderPipeline,
+    face_pipeline: wgpu::RenderPipeline,
+    overlay_pipeline: wgpu::RenderPipeline,
 
     camera_uniform: CameraUniform,
     projection: camera::Projection,
@@ -48,10 +49,14 @@ pub struct RenderState {
     render_faces: bool,
     faces: Option<FaceMesh>,
 
+    // Drawn over the scene while the model is being meshed
+    progress_overlay: Option<OverlayMesh>,
+    error_banner: Option<OverlayMesh>,
+    /// Error shown in the banner, kept to lay it out again when the window is resized
+    error: Option<String>,
+
     middle_mouse_pressed: bool,
     left_mouse_pressed: bool,
-
-    pub csg_func: Option<crate::CsgFunc>,
 }
 
 impl RenderState {
@@ -145,16 +150,73 @@ impl RenderState {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+        let create_pipeline = |label, front_face, cull_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "main",
+                    buffers: &[model::ModelVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "main",
+                    targets: &[wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face,
+                    cull_mode,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    clamp_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            })
+        };
+        let render_pipeline = create_pipeline("Render Pipeline", wgpu::FrontFace::Cw, None);
+        // Faces are oriented to wind counter-clockwise seen from outside of the shape
+        let face_pipeline =
+            create_pipeline("Face Pipeline", wgpu::FrontFace::Ccw, Some(wgpu::Face::Back));
+
+        // Overlays are drawn flat over everything else, without a camera
+        let overlay_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/overlay.wgsl").into()),
+        });
+        let overlay_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Overlay Pipeline Layout"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            });
+        let overlay_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&overlay_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: &overlay_shader,
                 entry_point: "main",
                 buffers: &[model::ModelVertex::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: &overlay_shader,
                 entry_point: "main",
                 targets: &[wgpu::ColorTargetState {
                     format: config.format,
@@ -165,8 +227,7 @@ impl RenderState {
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                // cull_mode: Some(wgpu::Face::Back),
+                front_face: wgpu::FrontFace::Ccw,
                 cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 clamp_depth: false,
@@ -174,8 +235,8 @@ impl RenderState {
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -195,6 +256,8 @@ impl RenderState {
             config,
 
             render_pipeline,
+            face_pipeline,
+            overlay_pipeline,
 
             projection,
             camera_uniform,
@@ -216,26 +279,65 @@ impl RenderState {
             render_faces: true,
             faces: None,
 
-            csg_func: None,
+            progress_overlay: None,
+            error_banner: None,
+            error: None,
         }
     }
 
-    pub fn set_csg_func(&mut self, func: CsgFunc) {
-        self.csg_func = Some(func);
+    pub fn set_octree_model(&mut self, outlines: &[Outline]) {
+        self.octants = Some(OctantMesh::new(&self.device, &self.queue, outlines));
     }
 
-    pub fn set_octree_model(&mut self, octants: Vec<Octant>) {
-        self.octants = Some(OctantMesh::new(&self.device, &self.queue, &octants));
-    }
-
-    pub fn set_faces_model(&mut self, faces: Vec<Face>) {
-        self.faces = Some(FaceMesh::new(&self.device, &self.queue, &faces));
+    pub fn set_faces_model(&mut self, triangles: Vec<Triangle>) {
+        self.faces = Some(FaceMesh::new(&self.device, &self.queue, &triangles));
     }
 
     pub fn set_points_model(&mut self, points: Vec<Point>) {
         self.points = Some(PointMesh::new(&self.device, &self.queue, &points));
     }
 
+    /// Shows a bar along the top of the window filled up to `completion`, hidden with `None`.
+    pub fn set_progress_overlay(&mut self, completion: Option<f32>) {
+        self.progress_overlay = completion.map(|completion| {
+            let (bottom, top) = (0.97, 1.0);
+            let filled = -1.0 + 2.0 * completion.clamp(0.0, 1.0);
+            let rectangles = [
+                Rectangle { lower: [-1.0, bottom], upper: [1.0, top], color: [0.2, 0.2, 0.2] },
+                Rectangle { lower: [-1.0, bottom], upper: [filled, top], color: [0.3, 0.7, 0.3] },
+            ];
+            OverlayMesh::new(&self.device, &self.queue, &rectangles)
+        });
+    }
+
+    /// Shows `error` in a red band along the bottom of the window, telling why the model failed
+    /// to load, hidden with `None`. Long errors wrap over several lines.
+    pub fn set_error_banner(&mut self, error: Option<&str>) {
+        self.error = error.map(str::to_owned);
+        self.layout_error_banner();
+    }
+
+    /// Lays out the error banner for the current size of the window.
+    fn layout_error_banner(&mut self) {
+        const SCALE: f32 = 2.0;
+        const MARGIN: f32 = 6.0;
+        let size = [self.config.width as f32, self.config.height as f32];
+        self.error_banner = self.error.as_ref().map(|error| {
+            let columns = (size[0] - 2.0 * MARGIN) / (text::ADVANCE as f32 * SCALE);
+            let lines = text::wrap(error, columns as usize);
+            let height = (lines.len() * text::LINE_HEIGHT) as f32 * SCALE + 2.0 * MARGIN;
+            let banner = Rectangle {
+                lower: [-1.0, -1.0],
+                upper: [1.0, -1.0 + 2.0 * height / size[1]],
+                color: [0.8, 0.15, 0.15],
+            };
+            let origin = [MARGIN, height - MARGIN];
+            let mut rectangles = vec![banner];
+            rectangles.extend(text::rectangles(&lines, origin, SCALE, size, [1.0, 1.0, 1.0]));
+            OverlayMesh::new(&self.device, &self.queue, &rectangles)
+        });
+    }
+
     pub fn update(&mut self, dt: std::time::Duration) {
         self.camera_controller.update_camera(&mut self.camera, dt);
         self.projection.resize(self.config.width, self.config.height);
@@ -255,6 +357,7 @@ impl RenderState {
             self.depth_texture =
                 texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
             self.surface.configure(&self.device, &self.config);
+            self.layout_error_banner();
         }
     }
 
@@ -369,9 +472,15 @@ impl RenderState {
 
             if let Some(faces) = &self.faces {
                 if self.render_faces {
+                    render_pass.set_pipeline(&self.face_pipeline);
                     render_pass.draw_faces(faces);
                 }
             }
+
+            render_pass.set_pipeline(&self.overlay_pipeline);
+            for overlay in self.progress_overlay.iter().chain(&self.error_banner) {
+                render_pass.draw_overlay(overlay);
+            }
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -1,14 +1,53 @@
-use {crate::types::Point, nalgebra::Vector3};
+use {
+    crate::types::{Aabb, Point},
+    nalgebra::Vector3,
+    std::sync::Arc,
+};
 
 type CsgTy = dyn Fn(f32, f32, f32) -> f32 + std::marker::Send + std::marker::Sync;
 
 pub struct CsgFunc {
     func: Box<CsgTy>,
+    node: Option<ShapeNode>,
+    bounds: Option<Aabb>,
+}
+
+/// How a shape was built, compared against the previous version of a model to find what changed.
+#[derive(Debug, Clone)]
+pub struct ShapeNode {
+    /// Name of the operation or primitive.
+    pub op: &'static str,
+    /// Numbers the operation was given, besides its shapes.
+    pub params: Vec<f32>,
+    pub children: Vec<Arc<CsgFunc>>,
+    /// Whether the children are in the same space as the shape, as with union and intersection,
+    /// so a change within one of them stays where it is.
+    pub children_in_place: bool,
+}
+
+/// Part of space where a shape may differ from its previous version, see `changed_region`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Unchanged,
+    Region(Aabb),
+    /// The change can't be narrowed down.
+    Everything,
+}
+
+impl Change {
+    /// Space covered by either of the changes.
+    pub fn join(self, other: Change) -> Change {
+        match (self, other) {
+            (Change::Unchanged, change) | (change, Change::Unchanged) => change,
+            (Change::Region(a), Change::Region(b)) => Change::Region(a.union(&b)),
+            _ => Change::Everything,
+        }
+    }
 }
 
 impl CsgFunc {
     pub fn new(func: Box<CsgTy>) -> Self {
-        CsgFunc { func }
+        CsgFunc { func, node: None, bounds: None }
     }
 
     pub fn call(&self, x: f32, y: f32, z: f32) -> f32 {
@@ -27,10 +66,59 @@ impl CsgFunc {
         )
         .normalize()
     }
+
+    /// Records how the shape was built, shapes without one are always taken as changed.
+    pub fn set_node(&mut self, node: ShapeNode) {
+        self.node = Some(node);
+    }
+
+    pub fn node(&self) -> Option<&ShapeNode> {
+        self.node.as_ref()
+    }
+
+    /// Sets a box containing every point within the shape, `None` (the default) if unbounded.
+    pub fn set_bounds(&mut self, bounds: Option<Aabb>) {
+        self.bounds = bounds;
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
 }
 
 impl std::fmt::Debug for CsgFunc {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("CsgFunc").finish()
+        f.debug_struct("CsgFunc").field("node", &self.node).field("bounds", &self.bounds).finish()
+    }
+}
+
+/// Finds where `current` may be inside while `previous` isn't, or the other way around, by
+/// diffing the trees the shapes were built from.
+///
+/// Subtrees built the same way are skipped, where they differ the region covers the bounds of both
+/// versions. Changes below a shape that moves its children, like scale, are widened to the bounds
+/// of that shape. Values outside of the region can still differ, but never across the surface.
+pub fn changed_region(previous: &CsgFunc, current: &CsgFunc) -> Change {
+    if std::ptr::eq(previous, current) {
+        return Change::Unchanged;
+    }
+    if let (Some(p), Some(c)) = (&previous.node, &current.node) {
+        if p.op == c.op && p.params == c.params && p.children.len() == c.children.len() {
+            let children = p
+                .children
+                .iter()
+                .zip(&c.children)
+                .map(|(p, c)| changed_region(p, c))
+                .fold(Change::Unchanged, Change::join);
+            match children {
+                Change::Unchanged => return Change::Unchanged,
+                change if p.children_in_place => return change,
+                _ => {}
+            }
+        }
+    }
+    match (previous.bounds, current.bounds) {
+        (Some(p), Some(c)) => Change::Region(p.union(&c)),
+        _ => Change::Everything,
     }
 }
@@ -1,11 +1,14 @@
 use {nalgebra::Vector3, std::ops::Sub};
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Face {
     Triangle { ul: Point, lr: Point, ll: Point },
     Plane { ul: Point, ur: Point, ll: Point, lr: Point },
 }
 
+/// Corners of a triangle, counter-clockwise when seen from outside of the shape.
+pub type Triangle = [Point; 3];
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Point {
     pub x: f32,
@@ -31,3 +34,76 @@ impl Sub for Point {
         Self { x: self.x - other.x, y: self.y - other.y, z: self.z - other.z }
     }
 }
+
+/// Axis aligned box, `lower` holds the smallest coordinates on every axis.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Aabb {
+    pub lower: Point,
+    pub upper: Point,
+}
+
+impl Aabb {
+    /// Creates the box spanned by two opposite corners.
+    pub fn new(a: Point, b: Point) -> Self {
+        Aabb {
+            lower: Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            upper: Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Point::new(
+                self.lower.x.min(other.lower.x),
+                self.lower.y.min(other.lower.y),
+                self.lower.z.min(other.lower.z),
+            ),
+            Point::new(
+                self.upper.x.max(other.upper.x),
+                self.upper.y.max(other.upper.y),
+                self.upper.z.max(other.upper.z),
+            ),
+        )
+    }
+
+    /// Part of the space covered by both boxes, flattened down to a single point on the axes
+    /// they don't overlap on.
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        let lower = Point::new(
+            self.lower.x.max(other.lower.x),
+            self.lower.y.max(other.lower.y),
+            self.lower.z.max(other.lower.z),
+        );
+        let upper = Point::new(
+            self.upper.x.min(other.upper.x).max(lower.x),
+            self.upper.y.min(other.upper.y).max(lower.y),
+            self.upper.z.min(other.upper.z).max(lower.z),
+        );
+        Aabb { lower, upper }
+    }
+
+    /// Returns a bool based on if the boxes overlap, boxes touching count as overlapping.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.lower.x <= other.upper.x
+            && other.lower.x <= self.upper.x
+            && self.lower.y <= other.upper.y
+            && other.lower.y <= self.upper.y
+            && self.lower.z <= other.upper.z
+            && other.lower.z <= self.upper.z
+    }
+
+    /// Grows the box by `margin` on every side.
+    pub fn expand(&self, margin: f32) -> Aabb {
+        Aabb {
+            lower: Point::new(self.lower.x - margin, self.lower.y - margin, self.lower.z - margin),
+            upper: Point::new(self.upper.x + margin, self.upper.y + margin, self.upper.z + margin),
+        }
+    }
+
+    /// Scales the box by `factor` about the origin.
+    pub fn scale(&self, factor: f32) -> Aabb {
+        let scale = |p: Point| Point::new(p.x * factor, p.y * factor, p.z * factor);
+        Aabb::new(scale(self.lower), scale(self.upper))
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher},
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        sync::mpsc::{channel, Receiver, RecvTimeoutError},
+        time::Duration,
+    },
+};
+
+/// Watches the files a model was loaded from, see `load::load_shape_tracking`.
+///
+/// Editors save in different ways: writing the file in place, renaming a new file over it, or
+/// removing it and creating it again. Watching the file itself would lose track of it in the last
+/// two cases, so the directories the files are in are watched instead, but not their
+/// subdirectories, and only events about the files count.
+pub struct ModelWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    files: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl ModelWatcher {
+    /// Events arriving within `delay` of each other are merged.
+    pub fn new(delay: Duration) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::watcher(sender, delay)?;
+        Ok(ModelWatcher { watcher, events, files: HashSet::new(), dirs: HashSet::new() })
+    }
+
+    /// Watches `files` from now on, instead of the files before.
+    ///
+    /// Every directory is tried even if some fail, the first error is returned. Directories that
+    /// could not be watched are tried again by the next call.
+    pub fn set_files(&mut self, files: &[PathBuf]) -> notify::Result<()> {
+        self.files = files.iter().map(|file| normalize(file)).collect();
+        let dirs: HashSet<PathBuf> =
+            self.files.iter().filter_map(|file| file.parent().map(Path::to_owned)).collect();
+        let mut first_error = None;
+        let unused: Vec<PathBuf> = self.dirs.difference(&dirs).cloned().collect();
+        for dir in unused {
+            // Forgotten either way, unwatching fails when the directory is gone
+            self.dirs.remove(&dir);
+            if let Err(error) = self.watcher.unwatch(&dir) {
+                first_error.get_or_insert(error);
+            }
+        }
+        let added: Vec<PathBuf> = dirs.difference(&self.dirs).cloned().collect();
+        for dir in added {
+            match self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir);
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Blocks until one of the files is saved, returning which. Returns `None` if the watcher
+    /// stopped.
+    pub fn wait(&self) -> Option<PathBuf> {
+        loop {
+            if let Some(path) = self.saved(self.events.recv().ok()?) {
+                return Some(path);
+            }
+        }
+    }
+
+    /// Same as `wait`, giving up after `timeout`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<PathBuf> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.events.recv_timeout(left) {
+                Ok(event) => {
+                    if let Some(path) = self.saved(event) {
+                        return Some(path);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    /// The file `event` saved, if it is one of ours. A removed file is only saved once it is
+    /// created again.
+    fn saved(&self, event: DebouncedEvent) -> Option<PathBuf> {
+        match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Rename(_, path) => {
+                let path = normalize(&path);
+                self.files.contains(&path).then_some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Absolute path of `file`, with links resolved in the directory but not the file itself, which
+/// may not exist while it is being replaced.
+fn normalize(file: &Path) -> PathBuf {
+    let dir = match file.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        None => return file.to_owned(),
+    };
+    match (dir.canonicalize(), file.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => file.to_owned(),
+    }
+}
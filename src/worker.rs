@@ -0,0 +1,184 @@
+use {
+    crate::{
+        event_loop::UserEvent,
+        mesher::MesherKind,
+        model::octants::Outline,
+        octree::Octree,
+        progress::Progress,
+        remesh::Remesher,
+        shape::CsgFunc,
+        types::{Point, Triangle},
+    },
+    log::info,
+    std::{
+        sync::mpsc::{channel, Receiver, SendError, Sender},
+        thread,
+    },
+    winit::event_loop::EventLoopProxy,
+};
+
+/// Requests for the meshing thread, see `spawn`.
+#[derive(Debug)]
+pub enum Job {
+    /// Mesh a new version of the model.
+    Shape(CsgFunc),
+    /// Mesh the model again at another resolution.
+    Resolution(f32),
+}
+
+/// Finished mesh of the model, ready to be uploaded by the event loop.
+#[derive(Debug)]
+pub struct Meshed {
+    pub triangles: Vec<Triangle>,
+    /// Outlines of the leaves of the octree, to show the octants.
+    pub outlines: Vec<Outline>,
+    pub points: Vec<Point>,
+}
+
+/// How the meshing thread renders the model.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub resolution: f32,
+    pub bound: f32,
+    pub tolerance: f32,
+    pub mesher: MesherKind,
+}
+
+/// Handle to the meshing thread, see `spawn`. Clones talk to the same thread.
+#[derive(Debug, Clone)]
+pub struct Worker {
+    jobs: Sender<Job>,
+    progress: Progress,
+}
+
+impl Worker {
+    /// Queues `job`, cancelling the job in progress since it is stale now.
+    pub fn send(&self, job: Job) -> Result<(), SendError<Job>> {
+        // Cancelled before sending, so the cancel can't hit the new job
+        self.progress.cancel();
+        self.jobs.send(job)
+    }
+
+    /// Cancels the job in progress, the previous mesh stays.
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    /// Progress of the job in progress, idle when there is none.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+}
+
+/// Starts a thread meshing the model in the background, so the window stays responsive.
+///
+/// Every finished mesh is sent to the event loop through `proxy`. Jobs arriving while the thread
+/// is busy cancel the job in progress and are merged, only the latest shape and resolution
+/// count. A cancelled job sends nothing and the next one renders the model from scratch. The
+/// thread stops once every `Worker` is dropped or the event loop is gone.
+pub fn spawn(proxy: EventLoopProxy<UserEvent>, settings: Settings) -> Worker {
+    spawn_with(move |meshed| proxy.send_event(UserEvent::Meshed(meshed)).is_ok(), settings)
+}
+
+/// Same as `spawn`, handing finished meshes to `send` instead, which returns false to stop.
+pub fn spawn_with(send: impl FnMut(Meshed) -> bool + Send + 'static, settings: Settings) -> Worker {
+    let (jobs, receiver) = channel();
+    let progress = Progress::new();
+    let worker_progress = progress.clone();
+    thread::spawn(move || run(send, settings, receiver, worker_progress));
+    Worker { jobs, progress }
+}
+
+/// State of the model on the meshing thread.
+enum Model {
+    None,
+    Meshed(Remesher),
+    /// Meshing was cancelled, the octree is incomplete.
+    Cancelled(CsgFunc),
+}
+
+fn run(
+    mut send: impl FnMut(Meshed) -> bool,
+    settings: Settings,
+    jobs: Receiver<Job>,
+    progress: Progress,
+) {
+    let mut model = Model::None;
+    let mut resolution = settings.resolution;
+    let mut pending: Vec<Job> = vec![];
+    let render = |csg_func: CsgFunc, resolution: f32| {
+        let mut octree = Octree::new(-settings.bound, settings.bound);
+        octree.set_tolerance(settings.tolerance);
+        octree.set_progress(progress.clone());
+        Remesher::new(octree, resolution, settings.mesher, csg_func)
+    };
+
+    loop {
+        if pending.is_empty() {
+            match jobs.recv() {
+                Ok(job) => pending.push(job),
+                Err(_) => return,
+            }
+        }
+        pending.extend(jobs.try_iter());
+        progress.reset();
+
+        // Only the latest of every kind of job is worth doing
+        let mut shape = None;
+        let mut new_resolution = None;
+        for job in pending.drain(..) {
+            match job {
+                Job::Shape(csg_func) => shape = Some(csg_func),
+                Job::Resolution(resolution) => new_resolution = Some(resolution),
+            }
+        }
+        if let Some(new_resolution) = new_resolution {
+            resolution = new_resolution;
+        }
+
+        let remesher = match (std::mem::replace(&mut model, Model::None), shape, new_resolution) {
+            (Model::Meshed(mut remesher), Some(csg_func), None) => {
+                let change = remesher.update(csg_func);
+                info!("Changed: {:?}", change);
+                remesher
+            }
+            (Model::Meshed(mut remesher), None, Some(resolution)) => {
+                remesher.set_resolution(resolution);
+                remesher
+            }
+            // Everything is rendered again anyway
+            (_, Some(csg_func), _) => render(csg_func, resolution),
+            (Model::Cancelled(csg_func), None, Some(_)) => render(csg_func, resolution),
+            // Nothing to mesh yet
+            (previous, None, _) => {
+                model = previous;
+                continue;
+            }
+        };
+
+        // Newer jobs make this mesh stale, skip straight to them
+        pending.extend(jobs.try_iter());
+        if progress.is_cancelled() {
+            info!("Meshing cancelled");
+            model = Model::Cancelled(remesher.into_csg_func());
+            progress.finish();
+            continue;
+        }
+        if !pending.is_empty() {
+            model = Model::Meshed(remesher);
+            continue;
+        }
+
+        let octree = remesher.octree();
+        let meshed = Meshed {
+            triangles: remesher.triangles(),
+            outlines: octree.leaves().map(Outline::from).collect(),
+            points: octree.leaves().flat_map(|o| o.feature_points()).collect(),
+        };
+        progress.finish();
+        model = Model::Meshed(remesher);
+        if !send(meshed) {
+            return;
+        }
+    }
+}
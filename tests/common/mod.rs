@@ -0,0 +1,38 @@
+//! Shapes and measurements shared by the tests, each test uses only some of them.
+#![allow(dead_code)]
+
+use conjure::{shape::CsgFunc, types::Triangle};
+
+/// Sphere of `radius` around the origin.
+pub fn sphere(radius: f32) -> CsgFunc {
+    CsgFunc::new(Box::new(move |x, y, z| (x * x + y * y + z * z).sqrt() - radius))
+}
+
+/// Sphere of `radius` around `(x, 0, 0)`.
+pub fn sphere_at(x: f32, radius: f32) -> CsgFunc {
+    CsgFunc::new(Box::new(move |px, y, z| ((px - x).powi(2) + y * y + z * z).sqrt() - radius))
+}
+
+/// Cube around the origin, `half` of its side wide in every direction.
+pub fn cube(half: f32) -> CsgFunc {
+    CsgFunc::new(Box::new(move |x, y, z| f32::max(f32::max(x.abs(), y.abs()), z.abs()) - half))
+}
+
+/// Signed volume enclosed by `triangles`, positive if they wind counter-clockwise seen from
+/// outside.
+pub fn volume(triangles: &[Triangle]) -> f32 {
+    triangles
+        .iter()
+        .map(|[a, b, c]| a.as_vector().dot(&b.as_vector().cross(&c.as_vector())) / 6.0)
+        .sum()
+}
+
+/// Triangles in a fixed order, so meshes can be compared regardless of extraction order.
+pub fn sorted(mut triangles: Vec<Triangle>) -> Vec<[u32; 9]> {
+    let key = |t: &Triangle| {
+        let [a, b, c] = t.map(|p| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]);
+        [a[0], a[1], a[2], b[0], b[1], b[2], c[0], c[1], c[2]]
+    };
+    triangles.sort_by_key(key);
+    triangles.iter().map(key).collect()
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use common::{cube, sphere};
+use conjure::{
+    dual_contour,
+    mesh::validate,
+    mesher,
+    octree::{Features, OctAxis, Octree},
+    shape::CsgFunc,
+    types::Point,
+};
+
+/// Splits `-bound..bound` into `cells` leaf sized axes.
+fn axes(bound: f32, cells: usize) -> Vec<(f32, f32)> {
+    let size = 2.0 * bound / cells as f32;
+    (0..cells).map(|i| (-bound + i as f32 * size, -bound + (i + 1) as f32 * size)).collect()
+}
+
+/// Features of a leaf cell, from its corners and hermite data sampled once, the way the octree
+/// does.
+fn features(x: (f32, f32), y: (f32, f32), z: (f32, f32), func: &CsgFunc) -> Features {
+    let (x, y, z) = (OctAxis::new(x.0, x.1), OctAxis::new(y.0, y.1), OctAxis::new(z.0, z.1));
+    let corners = dual_contour::sample_corners(x, y, z, func);
+    let hermite = dual_contour::hermite_edges(x, y, z, corners, func);
+    dual_contour::features_from_hermite(x, y, z, dual_contour::inside_mask(&corners), &hermite)
+}
+
+/// Point of the single feature of a leaf cell, see `features`.
+fn feature(x: (f32, f32), y: (f32, f32), z: (f32, f32), func: &CsgFunc) -> Point {
+    match &features(x, y, z, func)[..] {
+        [feature] => feature.point,
+        features => panic!("{} features instead of one", features.len()),
+    }
+}
+
+fn contains(x: (f32, f32), y: (f32, f32), z: (f32, f32), p: Point) -> bool {
+    (x.0..=x.1).contains(&p.x) && (y.0..=y.1).contains(&p.y) && (z.0..=z.1).contains(&p.z)
+}
+
+#[test]
+fn sphere_features_stay_in_cell() {
+    let func = sphere(3.3);
+    let mut count = 0;
+    for &x in &axes(4.0, 16) {
+        for &y in &axes(4.0, 16) {
+            for &z in &axes(4.0, 16) {
+                for p in features(x, y, z, &func).iter().map(|f| f.point) {
+                    count += 1;
+                    assert!(contains(x, y, z, p), "{:?} outside of {:?} {:?} {:?}", p, x, y, z);
+                    assert!(func.call_point(p).abs() < 0.1, "{:?} is far from the surface", p);
+                }
+            }
+        }
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn cube_edge_feature_lies_on_edge() {
+    // The cell straddles the edge of the cube along the z axis at x = y = 1
+    let func = cube(1.0);
+    let p = feature((0.8, 1.3), (0.7, 1.2), (0.1, 0.6), &func);
+    assert!(contains((0.8, 1.3), (0.7, 1.2), (0.1, 0.6), p));
+    assert!((p.x - 1.0).abs() < 0.01, "{:?}", p);
+    assert!((p.y - 1.0).abs() < 0.01, "{:?}", p);
+}
+
+#[test]
+fn cube_corner_feature_lies_on_corner() {
+    let func = cube(1.0);
+    let p = feature((0.7, 1.2), (0.8, 1.3), (0.9, 1.4), &func);
+    assert!((p.x - 1.0).abs() < 0.01, "{:?}", p);
+    assert!((p.y - 1.0).abs() < 0.01, "{:?}", p);
+    assert!((p.z - 1.0).abs() < 0.01, "{:?}", p);
+}
+
+#[test]
+fn cube_face_feature_is_clamped_to_cell() {
+    // Only parallel normals, the QEF is degenerate along the face
+    let func = cube(1.0);
+    let p = feature((0.9, 1.4), (0.2, 0.7), (-0.4, 0.1), &func);
+    assert!(contains((0.9, 1.4), (0.2, 0.7), (-0.4, 0.1), p), "{:?}", p);
+    assert!((p.x - 1.0).abs() < 0.01, "{:?}", p);
+}
+
+#[test]
+fn octree_features_stay_in_cell() {
+    let func = sphere(5.0);
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.render_shape(0.5, &func);
+    let faces = octree.extract_faces();
+    assert!(!faces.is_empty());
+    for octant in octree {
+        for p in octant.feature_points() {
+            assert!(contains(
+                (octant.x_axis.lower, octant.x_axis.upper),
+                (octant.y_axis.lower, octant.y_axis.upper),
+                (octant.z_axis.lower, octant.z_axis.upper),
+                p
+            ));
+        }
+    }
+}
+
+#[test]
+fn diagonal_corners_are_separate_sheets() {
+    // A single corner inside the shape is cut off by one sheet crossing three edges
+    let components = dual_contour::surface_components(0b0000_0001);
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].count_ones(), 3);
+
+    // Two corners on opposite ends of the cell, each one is its own sheet
+    let components = dual_contour::surface_components(0b1000_0001);
+    assert_eq!(components.len(), 2);
+    assert!(components.iter().all(|edges| edges.count_ones() == 3));
+
+    // Two corners diagonal on a face are kept apart
+    let components = dual_contour::surface_components(0b0000_1001);
+    assert_eq!(components.len(), 2);
+}
+
+#[test]
+fn thin_parts_mesh_as_manifolds() {
+    // A shell and a wall thinner than a cell, so cells hold several sheets of surface, each
+    // next to a ball whose smooth surface simplifies into larger leaves
+    let ball = |x: f32, y: f32, z: f32| ((x - 5.0).powi(2) + y * y + z * z).sqrt() - 2.5;
+    let shell = CsgFunc::new(Box::new(move |x, y, z| {
+        let shell = ((x * x + y * y + z * z).sqrt() - 2.1).abs() - 0.07;
+        f32::min(shell, ball(x, y, z))
+    }));
+    let wall = CsgFunc::new(Box::new(move |x, y, z| {
+        let across = (x + 0.4 * y + 0.3 * z + 1.13) / 1.25_f32.sqrt();
+        let wall = f32::max(across.abs() - 0.08, (x * x + y * y + z * z).sqrt() - 2.0);
+        f32::min(wall, ball(x, y, z))
+    }));
+
+    for (name, func) in [("shell", &shell), ("wall", &wall)] {
+        // Simplified trees have leaves of mixed sizes next to each other
+        for tolerance in [0.0, 0.05] {
+            let mut octree = Octree::new(-8.0, 8.0);
+            octree.set_tolerance(tolerance);
+            let handle = octree.render_shape(0.5, func);
+            assert!(octree.leaves().any(|leaf| leaf.features.len() > 1));
+            let sizes = |length: fn(f32) -> bool| {
+                octree.leaves().any(|leaf| length(leaf.x_axis.length()) && leaf.has_feature())
+            };
+            assert_eq!(sizes(|length| length > 0.5), tolerance > 0.0);
+            assert!(sizes(|length| length == 0.5));
+
+            let root = octree.shape_root(handle).unwrap();
+            let faces = dual_contour::cell_proc(&octree, root, |feature| feature.point);
+            let report = validate::validate(&mesher::triangulate(&faces, func));
+            assert!(report.triangles > 0);
+            assert!(report.is_watertight(), "{} at {}: {}", name, tolerance, report);
+            assert_eq!(
+                report.non_manifold_vertices.count, 0,
+                "{} at {}: {}",
+                name, tolerance, report
+            );
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn faces_do_not_depend_on_the_thread_count() {
+    // Sharp edges and corners where the cube cuts the sphere
+    let (ball, block) = (sphere(3.0), cube(2.3));
+    let func =
+        CsgFunc::new(Box::new(move |x, y, z| f32::max(ball.call(x, y, z), block.call(x, y, z))));
+    let extract = |threads| {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| {
+            let mut octree = Octree::new(-8.0, 8.0);
+            let handle = octree.render_shape(0.25, &func);
+            let root = octree.shape_root(handle).unwrap();
+            dual_contour::cell_proc(&octree, root, |feature| feature.point)
+        })
+    };
+
+    let faces = extract(1);
+    assert!(!faces.is_empty());
+    for threads in [2, 3, 8] {
+        assert!(extract(threads) == faces, "faces differ with {} threads", threads);
+    }
+}
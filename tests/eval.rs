@@ -0,0 +1,238 @@
+use conjure::lang::{self, Error, Reader, Ty};
+
+fn eval(source: &str) -> Result<Ty, Error> {
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    let (ty, spans) = Reader::read_spanned(source).map_err(Error::without_span)?;
+    lang::eval_spanned(ty, &spans, &env).map_err(Error::without_span)
+}
+
+#[test]
+fn malformed_forms_are_errors() {
+    assert!(matches!(eval("()"), Ok(Ty::List(list)) if list.is_empty()));
+    assert!(matches!(eval("(let [a 1])"), Err(Error::InvalidLetBinding)));
+    assert!(matches!(eval("(let)"), Err(Error::InvalidLetBinding)));
+    assert!(matches!(eval("(let [a] a)"), Err(Error::InvalidLetBinding)));
+    assert!(matches!(eval("(let [a 1] a a)"), Err(Error::InvalidLetBinding)));
+    assert!(matches!(eval("(sphere (+ 1 x))"), Err(Error::UnknownSymbol(s)) if s == "x"));
+    assert!(matches!(eval("(include)"), Err(Error::InvalidInclude)));
+    assert!(matches!(eval("(sphere 1"), Err(Error::FormEarlyEnd(..))));
+    assert!(matches!(eval("{:a"), Err(Error::FormEarlyEnd(..))));
+    assert!(matches!(eval(""), Err(Error::MissingForm)));
+
+    let map = eval("{:a 1 \"b\" [2]}").unwrap();
+    assert!(format!("{:?}", map).contains("Keyword(\"a\")"));
+}
+
+/// Evaluates the forms in `source` in order, returning the value of the last.
+fn eval_all(source: &str) -> Result<Ty, Error> {
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    let mut last = Ty::List(vec![]);
+    for (ty, spans) in Reader::read_all_spanned(source)? {
+        last = lang::eval_spanned(ty, &spans, &env)?;
+    }
+    Ok(last)
+}
+
+#[test]
+fn functions_are_defined_in_the_language() {
+    let number = |source| match eval_all(source) {
+        Ok(Ty::Number(n)) => n,
+        result => panic!("{} gave {:?}", source, result),
+    };
+    assert_eq!(number("((fn [a b] (- a b)) 5 3)"), 2.0);
+    assert_eq!(number("(defn add3 [a b c] (+ a b c)) (add3 1 2 3)"), 6.0);
+    // Functions see what was in scope where they were defined
+    assert_eq!(number("(def inc (let [one 1] (fn [n] (+ n one)))) (inc 4)"), 5.0);
+    assert_eq!(number("(defn twice [f x] (f (f x))) (defn inc [x] (+ x 1)) (twice inc 0)"), 2.0);
+    assert!(matches!(eval_all("(defn ball [r] (sphere r)) (ball 2)"), Ok(Ty::CsgFunc(_))));
+
+    let error = |source| eval_all(source).unwrap_err();
+    assert!(matches!(error("((fn [a] a) 1 2)").without_span(), Error::Arity(1, 2)));
+    assert!(matches!(error("(fn [1] 1)").without_span(), Error::InvalidFn));
+    assert!(matches!(error("(fn [a])").without_span(), Error::InvalidFn));
+    assert!(matches!(error("(defn f (a) a)").without_span(), Error::InvalidDefn));
+    let recursion = error("(defn forever [n] (forever n)) (forever 1)");
+    assert!(matches!(recursion.without_span(), Error::RecursionLimit(_)));
+
+    // Errors within the body point into it
+    let source = "(defn ball [r] (sphere size))\n(ball 1)";
+    assert_eq!(error(source).span(), Some(lang::Span::new(23, 27)));
+}
+
+#[test]
+fn functions_do_not_keep_their_scope_alive() {
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    let source = "(defn outer [] 1) (let [s (sphere 1) f (fn [] s) g (fn [] (f))] (g))";
+    let mut last = Ty::List(vec![]);
+    for (ty, spans) in Reader::read_all_spanned(source).unwrap() {
+        last = lang::eval_spanned(ty, &spans, &env).unwrap();
+    }
+    env.clear();
+    drop(env);
+    match last {
+        Ty::CsgFunc(shape) => assert_eq!(std::sync::Arc::strong_count(&shape), 1),
+        ty => panic!("{} is not a shape", ty),
+    }
+
+    // Later bindings in the same scope are out of reach, earlier ones and outer scopes aren't
+    assert!(eval_all("(let [f (fn [] g) g 1] (f))").is_err());
+    assert_eq!(eval_all("(let [a 1] (let [a 2 f (fn [] a)] (f)))").unwrap(), Ty::Number(2.0));
+    assert_eq!(eval_all("(defn f [] (g)) (defn g [] 3) (f)").unwrap(), Ty::Number(3.0));
+}
+
+#[test]
+fn conditions_pick_what_is_evaluated() {
+    let eval = |source| eval_all(source).unwrap();
+    assert_eq!(eval("(if (> 3 2) 1 2)"), Ty::Number(1.0));
+    assert_eq!(eval("(if (< 3 2) 1 2)"), Ty::Number(2.0));
+    assert_eq!(eval("(if false 1)"), Ty::List(vec![]));
+    assert_eq!(eval("(when (= 1 1) :yes)"), Ty::Keyword("yes".to_string()));
+    assert_eq!(eval("(when () :yes)"), Ty::List(vec![]));
+    assert_eq!(eval("(def x 5) (cond (< x 0) :neg (= x 0) :zero :else :pos)"), eval(":pos"));
+    assert_eq!(eval("(cond false 1)"), Ty::List(vec![]));
+
+    // Branches not taken are never evaluated
+    assert_eq!(eval("(if true 1 (undefined))"), Ty::Number(1.0));
+    assert_eq!(eval("(and 1 false (undefined))"), Ty::False);
+    assert_eq!(eval("(or false 2 (undefined))"), Ty::Number(2.0));
+    assert_eq!(eval("(and 1 2)"), Ty::Number(2.0));
+    assert_eq!(eval("(and)"), Ty::True);
+    assert_eq!(eval("(or)"), Ty::False);
+    assert_eq!(eval("(not ())"), Ty::True);
+    assert_eq!(eval("(not 0)"), Ty::False);
+
+    // Comparisons chain over every argument
+    assert_eq!(eval("(< 1 2 3)"), Ty::True);
+    assert_eq!(eval("(<= 1 1 0)"), Ty::False);
+    assert_eq!(eval("(>= 3 3 1)"), Ty::True);
+    assert_eq!(eval("(= [1 :a \"b\"] [1 :a \"b\"])"), Ty::True);
+    assert_eq!(eval("(= 1 1 2)"), Ty::False);
+    assert_eq!(eval("(let [s (sphere 1)] (= s s))"), Ty::True);
+    assert_eq!(eval("(= (sphere 1) (sphere 1))"), Ty::False);
+
+    // Parametric models
+    let model = "(defn part [wall] (if (> wall 2) (scale 2 (sphere wall)) (sphere wall))) (part 3)";
+    assert!(matches!(eval(model), Ty::CsgFunc(shape) if shape.call(5.0, 0.0, 0.0) < 0.0));
+
+    let error = |source| eval_all(source).unwrap_err().without_span();
+    assert!(matches!(error("(< 1 :a)"), Error::InvalidType(Ty::Keyword(_))));
+    assert!(matches!(error("(<)"), Error::MinArity(1, 0)));
+    assert!(matches!(error("(not 1 2)"), Error::Arity(1, 2)));
+    assert!(matches!(error("(if true)"), Error::InvalidIf));
+    assert!(matches!(error("(if true 1 2 3)"), Error::InvalidIf));
+    assert!(matches!(error("(when true)"), Error::InvalidWhen));
+    assert!(matches!(error("(cond true)"), Error::InvalidCond));
+}
+
+#[test]
+fn math_computes_positions() {
+    let eval = |source| match eval_all(source).unwrap() {
+        Ty::Number(n) => n,
+        ty => panic!("{} is not a number", ty),
+    };
+    assert_eq!(eval("(* 2 3 4)"), 24.0);
+    assert_eq!(eval("(*)"), 1.0);
+    assert_eq!(eval("(- 5)"), -5.0);
+    assert_eq!(eval("(- 10 1 2)"), 7.0);
+    assert_eq!(eval("(/ 4)"), 0.25);
+    assert_eq!(eval("(/ 12 2 3)"), 2.0);
+    assert_eq!(eval("(mod -1 3)"), 2.0);
+    assert_eq!(eval("(mod 7 -3)"), -2.0);
+    assert_eq!(eval("(min 3 -1 2)"), -1.0);
+    assert_eq!(eval("(max 3 -1 2)"), 3.0);
+    assert_eq!(eval("(abs -2)"), 2.0);
+    assert_eq!(eval("(sqrt 16)"), 4.0);
+    assert_eq!(eval("(pow 2 10)"), 1024.0);
+    assert_eq!(eval("(floor -1.5)"), -2.0);
+    assert_eq!(eval("(ceil 1.2)"), 2.0);
+    assert_eq!(eval("(round 2.5)"), 3.0);
+    assert_eq!(eval("(deg->rad 180)"), std::f32::consts::PI);
+    assert!((eval("(sin (/ pi 2))") - 1.0).abs() < 1e-6);
+    assert!((eval("(cos pi)") + 1.0).abs() < 1e-6);
+    assert!((eval("(tan (/ pi 4))") - 1.0).abs() < 1e-6);
+    assert!((eval("(atan2 1 0)") - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+
+    // Vectors evaluate their elements, so corners can be computed
+    let model = "(def w 2) (cube [(- w) (- w) 0] [w w (* w 3)])";
+    match eval_all(model).unwrap() {
+        Ty::CsgFunc(shape) => {
+            assert!(shape.call(0.0, 0.0, 5.0) < 0.0);
+            assert!(shape.call(0.0, 0.0, 7.0) > 0.0);
+        }
+        ty => panic!("{} is not a shape", ty),
+    }
+
+    let error = |source| eval_all(source).unwrap_err().without_span();
+    assert!(matches!(error("(-)"), Error::MinArity(1, 0)));
+    assert!(matches!(error("(min)"), Error::MinArity(1, 0)));
+    assert!(matches!(error("(sqrt)"), Error::Arity(1, 0)));
+    assert!(matches!(error("(sqrt 1 2)"), Error::Arity(1, 2)));
+    assert!(matches!(error("(pow 2)"), Error::Arity(2, 1)));
+    assert!(matches!(error("(* 2 :a)"), Error::InvalidType(Ty::Keyword(_))));
+    assert!(matches!(error("(- \"s\")"), Error::InvalidType(Ty::Str(_))));
+    assert_eq!(error("(atan2 1)").to_string(), "Expected 2 arguments but got 1");
+    assert_eq!(error("(abs 1 2)").to_string(), "Expected 1 argument but got 2");
+}
+
+#[test]
+fn nesting_is_limited() {
+    // Deep enough to overflow the stack if it weren't
+    for open in ["(", "[", "{"] {
+        let error = eval_all(&open.repeat(100_000)).unwrap_err();
+        assert!(matches!(error.without_span(), Error::NestingLimit(_)));
+    }
+
+    // Up to the limit is fine
+    let nested = |depth| format!("{}0{}", "(+ 1 ".repeat(depth), ")".repeat(depth));
+    assert_eq!(eval_all(&nested(250)).unwrap(), Ty::Number(250.0));
+    assert!(matches!(eval_all(&nested(300)).unwrap_err().without_span(), Error::NestingLimit(_)));
+
+    // Forms in the functions called count too
+    let body = format!("{}(f (- n 1)){}", "(+ 1 ".repeat(200), ")".repeat(200));
+    let source = format!("(defn f [n] (if (> n 0) {} 0)) (f 1)", body);
+    assert_eq!(eval_all(&source).unwrap(), Ty::Number(200.0));
+    let error = eval_all(&source.replace("(f 1)", "(f 5)")).unwrap_err();
+    assert!(matches!(error.without_span(), Error::NestingLimit(_)));
+}
+
+#[test]
+fn includes_can_be_forbidden() {
+    let env = lang::Env::new();
+    env.forbid_includes();
+    let (ty, spans) = Reader::read_spanned("(include \"/dev/zero\")").unwrap();
+    let error = lang::eval_spanned(ty, &spans, &env).unwrap_err();
+    assert!(matches!(error.without_span(), Error::IncludeForbidden));
+}
+
+/// Same as the fuzz target (see `fuzz/`), over random sequences of tokens.
+#[test]
+fn random_sources_never_panic() {
+    const TOKENS: &[&str] = &[
+        "(", ")", "[", "]", "{", "}", "let", "sphere", "cube", "union", "+", "-", "1", "-2.5",
+        ":k", "\"s\"", "\"", "a", "true", ";", "\n", "()", "'", "@", "fn", "defn", "def", "[a]",
+        "if", "cond", "and", "<", "=", "*", "/", "mod", "pi", "sqrt",
+    ];
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    for _ in 0..2000 {
+        let len = random() % 16;
+        let source: Vec<&str> = (0..len).map(|_| TOKENS[random() % TOKENS.len()]).collect();
+        if let Ok(ty) = eval_all(&source.join(" ")) {
+            let _ = format!("{:?} {}", ty, ty);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use {
+    conjure::load::{self, LoadError, Location},
+    std::path::PathBuf,
+};
+
+/// Writes `source` to a file of its own in the temporary directory.
+fn write(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("conjure-load-{}.cnj", name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn shapes_load() {
+    let path = write("shape", "; a ball\n(sphere 4)");
+    let csg_func = load::load_shape(&path).unwrap();
+    assert!(csg_func.call(0.0, 0.0, 0.0) < 0.0);
+}
+
+#[test]
+fn errors_are_located_in_the_file() {
+    let path = write("unknown", "; a typo\n\n  (spher 4)");
+    let error = load::load_shape(&path).unwrap_err();
+    assert_eq!(error.location(), Some(Location { line: 3, column: 4 }));
+    let message = error.to_string();
+    assert!(message.starts_with(&format!("{}:3:4: ", path.display())), "{}", message);
+    assert!(message.contains("spher"), "{}", message);
+
+    // Unterminated forms used to run off the end of the tokens
+    let path = write("unterminated", "(union\n  (sphere 4)\n  (cube [1 2 3] [4 5 6)");
+    let error = load::load_shape(&path).unwrap_err();
+    assert!(matches!(error.location(), Some(Location { line: 3, .. })), "{}", error);
+
+    // Editors may truncate the file before writing it
+    for (name, source) in [("empty", ""), ("comment", "; nothing yet\n")] {
+        assert!(load::load_shape(&write(name, source)).is_err());
+    }
+
+    let path = write("number", "4");
+    assert!(matches!(load::load_shape(&path), Err(LoadError::NotAShape(_))));
+
+    let missing = std::env::temp_dir().join("conjure-load-missing.cnj");
+    assert!(matches!(load::load_shape(&missing), Err(LoadError::Io(..))));
+}
+
+#[test]
+fn diagnostics_point_at_the_source() {
+    let path = write("diagnostic", "(union\n  (sphere 4)\n  (scale size (cube [1 2 3] [4 5 6])))");
+    let error = load::load_shape(&path).unwrap_err();
+    let expected = format!(
+        "error: Symbol 'size' has not been defined
+ --> {}:3:10
+  |
+3 |   (scale size (cube [1 2 3] [4 5 6])))
+  |          ^^^^
+",
+        path.display()
+    );
+    assert_eq!(error.diagnostic(), expected);
+
+    // Errors from functions point at the whole call
+    let path = write("call", "(sphere \"big\")");
+    let diagnostic = load::load_shape(&path).unwrap_err().diagnostic();
+    assert!(diagnostic.ends_with("1 | (sphere \"big\")\n  | ^^^^^^^^^^^^^^\n"), "{}", diagnostic);
+}
+
+#[test]
+fn locations_count_lines_and_characters() {
+    let source = "(a\n  é b)";
+    assert_eq!(Location::of(source, 0), Location { line: 1, column: 1 });
+    assert_eq!(Location::of(source, source.find('b').unwrap()), Location { line: 2, column: 5 });
+}
+
+#[test]
+fn includes_are_read_relative_to_the_file() {
+    let dir = std::env::temp_dir().join("conjure-load-include");
+    std::fs::create_dir_all(dir.join("parts")).unwrap();
+    std::fs::write(dir.join("parts/ball.cnj"), "(sphere 4)").unwrap();
+    std::fs::write(dir.join("parts/broken.cnj"), "\n (spher 4)").unwrap();
+    std::fs::write(dir.join("parts/loop.cnj"), r#"(include "../model.cnj")"#).unwrap();
+
+    let model = dir.join("model.cnj");
+    std::fs::write(&model, r#"(union (include "parts/ball.cnj") (cube [5 5 5] [6 6 6]))"#).unwrap();
+    let mut files = vec![];
+    let csg_func = load::load_shape_tracking(&model, &mut files).unwrap();
+    assert!(csg_func.call(0.0, 0.0, 0.0) < 0.0);
+    assert_eq!(files, [model.clone(), dir.join("parts/ball.cnj")]);
+
+    // Errors point into the included file, which is still tracked
+    std::fs::write(&model, r#"(include "parts/broken.cnj")"#).unwrap();
+    let mut files = vec![];
+    let error = load::load_shape_tracking(&model, &mut files).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with(&format!("{}:2:3: ", dir.join("parts/broken.cnj").display())));
+    assert_eq!(files, [model.clone(), dir.join("parts/broken.cnj")]);
+
+    std::fs::write(&model, r#"(include "parts/loop.cnj")"#).unwrap();
+    let error = load::load_shape(&model).unwrap_err();
+    assert!(error.to_string().contains("includes itself"), "{}", error);
+}
+
+#[test]
+fn the_model_is_the_last_shape_or_the_rendered_one() {
+    let inside = |source: &str, name: &str, point: [f32; 3]| {
+        let csg_func = load::load_shape(&write(name, source)).unwrap();
+        csg_func.call(point[0], point[1], point[2]) < 0.0
+    };
+
+    // Definitions are visible to every later form, even from within a let
+    let defs = "(def r 2)\n(let [s (sphere r)] (def ball s))\n(scale 2 ball)\n(def unused 1)";
+    assert!(inside(defs, "defs", [3.0, 0.0, 0.0]));
+
+    let two = "(sphere 1)\n(cube [4 4 4] [5 5 5])";
+    assert!(inside(two, "two", [4.5, 4.5, 4.5]));
+    assert!(!inside(two, "two", [0.0, 0.0, 0.0]));
+
+    let rendered = "(render (sphere 1))\n(cube [4 4 4] [5 5 5])";
+    assert!(inside(rendered, "rendered", [0.0, 0.0, 0.0]));
+    assert!(!inside(rendered, "rendered", [4.5, 4.5, 4.5]));
+
+    let path = write("no-shape", "(def r 1)\n; nothing to see");
+    assert!(matches!(load::load_shape(&path), Err(LoadError::NotAShape(_))));
+
+    // A stray bracket between forms is an error rather than a symbol
+    let error = load::load_shape(&write("stray", "(sphere 1))\n(sphere 2)")).unwrap_err();
+    assert_eq!(error.location(), Some(Location { line: 1, column: 11 }));
+    let error = load::load_shape(&write("bad-def", "(def 1 2)")).unwrap_err();
+    assert!(error.to_string().ends_with("Def expects a name and a value"), "{}", error);
+}
+
+#[test]
+fn functions_can_come_from_included_files() {
+    let dir = std::env::temp_dir().join("conjure-load-library");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("parts.cnj"), "(defn ball [r]\n  (sphere (+ r offset)))").unwrap();
+
+    let model = dir.join("model.cnj");
+    std::fs::write(&model, "(include \"parts.cnj\")\n(def offset 1)\n(ball 2)").unwrap();
+    let csg_func = load::load_shape(&model).unwrap();
+    assert!(csg_func.call(2.5, 0.0, 0.0) < 0.0);
+
+    // Errors within a function from another file point at the call
+    std::fs::write(&model, "(include \"parts.cnj\")\n(ball 2)").unwrap();
+    let error = load::load_shape(&model).unwrap_err();
+    assert_eq!(error.location(), Some(Location { line: 2, column: 1 }));
+    assert!(error.to_string().contains("offset"), "{}", error);
+}
+
+#[test]
+fn shapes_from_let_bound_functions_load() {
+    // The shape is bound in a scope the function keeps
+    let source = "(let [s (sphere 4) f (fn [] s) g (fn [] (f))] (g))";
+    let csg_func = load::load_shape(&write("let-fn", source)).unwrap();
+    assert!(csg_func.call(3.5, 0.0, 0.0) < 0.0);
+}
@@ -0,0 +1,70 @@
+use {
+    conjure::{mass::MassProperties, mesher, octree::Octree, shape::CsgFunc, types::Point},
+    std::f32::consts::PI,
+};
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance * expected.abs().max(1.0),
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+/// Both ways of measuring `csg_func`, rendered at a resolution of 0.5.
+fn measure(csg_func: &CsgFunc) -> [MassProperties; 2] {
+    let mut octree = Octree::new(-8.0, 8.0);
+    let handle = octree.render_shape(0.5, csg_func);
+    let triangles = mesher::triangulate(&octree.extract_faces(), csg_func);
+    [MassProperties::from_mesh(&triangles), MassProperties::from_octree(&octree, handle, csg_func)]
+}
+
+#[test]
+fn sphere_properties() {
+    let sphere = CsgFunc::new(Box::new(|x, y, z| {
+        ((x - 1.0).powi(2) + y * y + (z + 0.5).powi(2)).sqrt() - 5.0
+    }));
+    let volume = 4.0 / 3.0 * PI * 125.0;
+    for properties in measure(&sphere) {
+        assert_close(properties.volume, volume, 0.005);
+        assert_close(properties.surface_area, 4.0 * PI * 25.0, 0.01);
+        assert_close(properties.centroid.x, 1.0, 0.001);
+        assert_close(properties.centroid.y, 0.0, 0.001);
+        assert_close(properties.centroid.z, -0.5, 0.001);
+        for i in 0..3 {
+            assert_close(properties.inertia[(i, i)], 0.4 * volume * 25.0, 0.01);
+        }
+    }
+}
+
+#[test]
+fn box_properties_scale_with_density() {
+    let cuboid = CsgFunc::new(Box::new(|x, y, z| {
+        f32::max(f32::max((x - 0.3).abs() - 2.0, y.abs() - 3.0), z.abs() - 4.1)
+    }));
+    let (a, b, c) = (4.0, 6.0, 8.2);
+    let volume = a * b * c;
+    for properties in measure(&cuboid) {
+        assert_close(properties.volume, volume, 0.005);
+        assert_close(properties.surface_area, 2.0 * (a * b + b * c + c * a), 0.01);
+        assert_close(properties.centroid.y, 0.0, 0.001);
+        assert_close(properties.centroid.x, 0.3, 0.001);
+        assert_close(properties.mass(2.0), 2.0 * volume, 0.005);
+
+        let inertia = properties.inertia(2.0);
+        let mass = 2.0 * volume;
+        assert_close(inertia[(0, 0)], mass / 12.0 * (b * b + c * c), 0.01);
+        assert_close(inertia[(1, 1)], mass / 12.0 * (a * a + c * c), 0.01);
+        assert_close(inertia[(2, 2)], mass / 12.0 * (a * a + b * b), 0.01);
+        assert_close(inertia[(0, 1)], 0.0, 0.001);
+    }
+}
+
+#[test]
+fn empty_mesh_has_no_mass() {
+    let properties = MassProperties::from_mesh(&[]);
+    assert_eq!(properties.volume, 0.0);
+    assert_eq!(properties.centroid, Point::new(0.0, 0.0, 0.0));
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{cube, sphere, volume};
+use conjure::{
+    mesh::validate,
+    mesher::{self, Mesher, MesherKind},
+    octree::Octree,
+    shape::CsgFunc,
+    types::{Point, Triangle},
+};
+
+#[test]
+fn split_quad_follows_the_surface() {
+    // A ridge along x = z: the quad has to fold along the ul-lr diagonal to stay on it
+    let ridge = CsgFunc::new(Box::new(|x, y, z| y - (1.0 - (x - z).abs())));
+    let (ul, ur, ll, lr) = (
+        Point::new(0.0, 1.0, 0.0),
+        Point::new(1.0, 0.0, 0.0),
+        Point::new(0.0, 0.0, 1.0),
+        Point::new(1.0, 1.0, 1.0),
+    );
+    let [a, b] = mesher::split_quad(ul, ur, ll, lr, &ridge);
+    assert_eq!([a, b], [[ul, ur, lr], [lr, ll, ul]]);
+
+    // Starting the loop one corner later puts the ridge on the ur-ll diagonal
+    let [a, b] = mesher::split_quad(ur, lr, ul, ll, &ridge);
+    assert_eq!([a, b], [[lr, ll, ul], [ul, ur, lr]]);
+}
+
+#[test]
+fn triangulated_sphere_keeps_its_volume() {
+    let func = sphere(5.0);
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.render_shape(0.5, &func);
+    let faces = MesherKind::DualContouring.extract_faces(&octree);
+    let triangles = mesher::triangulate(&faces, &func);
+    let expected = 4.0 / 3.0 * std::f32::consts::PI * 5.0_f32.powi(3);
+    assert!((volume(&triangles) - expected).abs() / expected < 0.02);
+}
+
+#[test]
+fn every_mesher_closes_a_sphere() {
+    let func = sphere(5.0);
+    let expected = 4.0 / 3.0 * std::f32::consts::PI * 5.0_f32.powi(3);
+    for kind in [MesherKind::DualContouring, MesherKind::MarchingCubes, MesherKind::SurfaceNets] {
+        let mut octree = Octree::new(-8.0, 8.0);
+        octree.render_shape(0.5, &func);
+        let triangles = mesher::triangulate(&kind.extract_faces(&octree), &func);
+
+        let report = validate::validate(&triangles);
+        assert!(report.is_watertight(), "{}: {}", kind, report);
+        assert_eq!(report.non_manifold_vertices.count, 0, "{}: {}", kind, report);
+        let error = (volume(&triangles) - expected).abs() / expected;
+        assert!(error < 0.03, "{} is off by {}", kind, error);
+    }
+}
+
+#[test]
+fn marching_cubes_needs_one_leaf_size() {
+    assert!(!MesherKind::MarchingCubes.supports_simplification());
+    assert!(MesherKind::DualContouring.supports_simplification());
+    assert!(MesherKind::SurfaceNets.supports_simplification());
+
+    // Regions far from the surface are still merged into larger leaves, the surface is not
+    let func = sphere(3.3);
+    let mut octree = Octree::new(-8.0, 8.0);
+    let handle = octree.render_shape(0.5, &func);
+    assert!(octree.shape_leaves(handle).any(|leaf| leaf.x_axis.length() > 0.5));
+    assert!(octree
+        .shape_leaves(handle)
+        .filter(|leaf| leaf.inside() != 0 && leaf.inside() != 0xff)
+        .all(|leaf| leaf.x_axis.length() == 0.5));
+    let faces = MesherKind::MarchingCubes.extract_shape_faces(&octree, handle);
+    let report = validate::validate(&mesher::triangulate(&faces, &func));
+    assert!(report.is_watertight(), "{}", report);
+}
+
+#[test]
+fn faces_point_along_the_gradient() {
+    // The cube cuts the sphere, folding faces over sharp edges and corners
+    let (ball, block) = (sphere(3.0), cube(2.3));
+    let cut =
+        CsgFunc::new(Box::new(move |x, y, z| f32::max(ball.call(x, y, z), block.call(x, y, z))));
+    let kinds = [MesherKind::DualContouring, MesherKind::SurfaceNets, MesherKind::MarchingCubes];
+    for (name, func) in [("sphere", &sphere(5.0)), ("cube", &cube(3.0)), ("cut", &cut)] {
+        for (kind, tolerance) in kinds.iter().flat_map(|&kind| [(kind, 0.0), (kind, 0.05)]) {
+            if tolerance > 0.0 && !kind.supports_simplification() {
+                continue;
+            }
+            let mut octree = Octree::new(-8.0, 8.0);
+            octree.set_tolerance(tolerance);
+            octree.render_shape(0.5, func);
+            let triangles = mesher::triangulate(&kind.extract_faces(&octree), func);
+            assert!(volume(&triangles) > 0.0, "{} {} at {}", name, kind, tolerance);
+
+            for [a, b, c] in &triangles {
+                let normal = (*b - *a).as_vector().cross(&(*c - *a).as_vector());
+                if normal.norm() < 1e-6 {
+                    // Marching cubes pinches triangles where the surface runs through a corner
+                    continue;
+                }
+                let centroid = (a.as_vector() + b.as_vector() + c.as_vector()) / 3.0;
+                let gradient = func.normal(centroid.x, centroid.y, centroid.z);
+                assert!(
+                    normal.dot(&gradient) > 0.0,
+                    "{} {} at {}: {:?} faces inward",
+                    name,
+                    kind,
+                    tolerance,
+                    [a, b, c]
+                );
+            }
+        }
+    }
+}
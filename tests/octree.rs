@@ -0,0 +1,66 @@
+mod common;
+
+use common::{cube, sphere_at};
+use conjure::{octree::Octree, query, shape::CsgFunc, types::Point};
+
+/// Faces of `csg_func` rendered on its own.
+fn faces_alone(csg_func: &CsgFunc) -> usize {
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.render_shape(0.5, csg_func);
+    octree.extract_faces().len()
+}
+
+#[test]
+fn shapes_get_their_own_handles() {
+    let (left, right) = (sphere_at(-4.0, 2.0), cube(1.5));
+    let mut octree = Octree::new(-8.0, 8.0);
+    let a = octree.render_shape(0.5, &left);
+    let b = octree.render_shape(0.5, &right);
+    assert_ne!(a, b);
+    assert_eq!(octree.handles().collect::<Vec<_>>(), vec![a, b]);
+
+    // Every leaf knows the shape it belongs to
+    assert!(octree.shape_leaves(a).all(|leaf| leaf.shape == a));
+    assert!(octree.shape_leaves(b).all(|leaf| leaf.shape == b));
+    assert!(octree.shape_leaves(b).any(|leaf| leaf.has_feature()));
+
+    let (faces_a, faces_b) = (octree.extract_shape_faces(a), octree.extract_shape_faces(b));
+    assert_eq!(faces_a.len(), faces_alone(&left));
+    assert_eq!(faces_b.len(), faces_alone(&right));
+    assert_eq!(octree.extract_faces().len(), faces_a.len() + faces_b.len());
+
+    // Queries see every shape
+    assert_eq!(query::inside(&octree, Point::new(-4.0, 0.0, 0.0)), Some(true));
+    assert_eq!(query::inside(&octree, Point::new(0.0, 0.0, 0.0)), Some(true));
+    assert_eq!(query::inside(&octree, Point::new(-2.0, 0.0, 0.0)), Some(false));
+}
+
+#[test]
+fn shapes_can_be_removed_and_replaced() {
+    let mut octree = Octree::new(-8.0, 8.0);
+    let a = octree.render_shape(0.5, &sphere_at(-4.0, 2.0));
+    let b = octree.render_shape(0.5, &cube(1.5));
+    let faces_b = octree.extract_shape_faces(b);
+
+    assert!(octree.remove_shape(a));
+    assert!(!octree.remove_shape(a));
+    assert!(octree.extract_shape_faces(a).is_empty());
+    assert_eq!(octree.extract_shape_faces(b), faces_b);
+    assert_eq!(octree.handles().collect::<Vec<_>>(), vec![b]);
+    assert_eq!(query::inside(&octree, Point::new(-4.0, 0.0, 0.0)), Some(false));
+
+    // Handles are not reused
+    let c = octree.render_shape(0.5, &sphere_at(4.0, 1.0));
+    assert!(c != a && c != b);
+
+    let replacement = sphere_at(0.0, 3.0);
+    assert!(octree.replace_shape(b, 0.5, &replacement));
+    assert!(!octree.replace_shape(a, 0.5, &replacement));
+    assert_eq!(octree.handles().collect::<Vec<_>>(), vec![b, c]);
+    assert_eq!(octree.extract_shape_faces(b).len(), faces_alone(&replacement));
+    assert!(octree.shape_leaves(c).all(|leaf| leaf.shape == c));
+    assert_eq!(
+        octree.leaves().count(),
+        octree.shape_leaves(b).count() + octree.shape_leaves(c).count()
+    );
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::sphere;
+use conjure::{
+    mesher::{Mesher, MesherKind},
+    octree::Octree,
+    progress::{Progress, Stage},
+    shape::CsgFunc,
+};
+
+#[test]
+fn renders_report_their_progress() {
+    let progress = Progress::new();
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.set_progress(progress.clone());
+    assert_eq!(progress.stage(), Stage::Idle);
+
+    let handle = octree.render_shape(0.5, &sphere(4.0));
+    assert_eq!(progress.stage(), Stage::Rendering);
+    assert!((progress.completion() - 1.0).abs() < 1e-3, "{}", progress.completion());
+    assert!(progress.visited() >= octree.leaves().count());
+
+    MesherKind::DualContouring.extract_shape_faces(&octree, handle);
+    assert_eq!(progress.stage(), Stage::Extracting);
+    assert!((progress.completion() - 1.0).abs() < 1e-3, "{}", progress.completion());
+
+    progress.finish();
+    assert_eq!(progress.stage(), Stage::Idle);
+    assert_eq!(progress.visited(), 0);
+}
+
+#[test]
+fn cancelled_renders_stop_early() {
+    let mut full = Octree::new(-8.0, 8.0);
+    full.render_shape(0.25, &sphere(4.0));
+
+    let progress = Progress::new();
+    progress.cancel();
+    let mut cancelled = Octree::new(-8.0, 8.0);
+    cancelled.set_progress(progress.clone());
+    let handle = cancelled.render_shape(0.25, &sphere(4.0));
+    assert!(cancelled.leaves().count() < full.leaves().count() / 10);
+
+    // Extraction gives up as well
+    assert!(MesherKind::DualContouring.extract_shape_faces(&cancelled, handle).is_empty());
+
+    progress.reset();
+    assert!(!progress.is_cancelled());
+}
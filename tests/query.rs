@@ -0,0 +1,86 @@
+mod common;
+
+use common::{cube, sphere};
+use {
+    conjure::{
+        octree::Octree,
+        query::{self, Ray},
+        shape::CsgFunc,
+        types::Point,
+    },
+    nalgebra::Vector3,
+};
+
+fn render(csg_func: &CsgFunc) -> Octree {
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.render_shape(0.25, csg_func);
+    octree
+}
+
+#[test]
+fn raymarch_hits_the_closest_surface() {
+    let ray = Ray::new(Point::new(-10.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0));
+    let hit = query::raymarch(&sphere(3.0), &ray, 100.0).unwrap();
+    assert!((hit.distance - 7.0).abs() < 1e-3);
+    assert!((hit.point.x + 3.0).abs() < 1e-3);
+    assert!((hit.normal - Vector3::new(-1.0, 0.0, 0.0)).norm() < 1e-2);
+
+    // From within the shape the ray finds where it leaves it
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+    let hit = query::raymarch(&sphere(3.0), &ray, 100.0).unwrap();
+    assert!((hit.point.z - 3.0).abs() < 1e-3);
+
+    let away = Ray::new(Point::new(-10.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+    assert_eq!(query::raymarch(&sphere(3.0), &away, 100.0), None);
+    assert_eq!(query::raymarch(&sphere(3.0), &ray, 2.0), None);
+}
+
+#[test]
+fn raymarch_handles_overestimated_distances() {
+    // Scaling the field up makes every step too long, the first one ends within the sphere
+    let scaled = CsgFunc::new(Box::new(|x, y, z| ((x * x + y * y + z * z).sqrt() - 3.0) * 1.5));
+    let ray = Ray::new(Point::new(-10.0, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0));
+    let hit = query::raymarch(&scaled, &ray, 100.0).unwrap();
+    let expected = -(9.0_f32 - 0.25).sqrt();
+    assert!((hit.point.x - expected).abs() < 1e-3, "{:?}", hit);
+}
+
+#[test]
+fn octree_point_queries() {
+    let csg_func = cube(3.0);
+    let octree = render(&csg_func);
+
+    assert_eq!(query::inside(&octree, Point::new(0.0, 2.9, 0.0)), Some(true));
+    assert_eq!(query::inside(&octree, Point::new(0.0, 3.1, 0.0)), Some(false));
+    assert_eq!(query::inside(&octree, Point::new(0.0, 9.0, 0.0)), None);
+
+    let value = query::value(&octree, Point::new(1.3, -0.2, 2.1)).unwrap();
+    assert!((value - csg_func.call(1.3, -0.2, 2.1)).abs() < 0.01);
+
+    // Sharp corners are kept
+    let corner = query::nearest_point(&octree, Point::new(5.0, 5.0, 5.0)).unwrap();
+    assert!((corner - Point::new(3.0, 3.0, 3.0)).as_vector().norm() < 1e-3, "{:?}", corner);
+    let distance = query::distance(&octree, Point::new(5.0, 5.0, 5.0)).unwrap();
+    assert!((distance - 12.0_f32.sqrt()).abs() < 1e-3);
+}
+
+#[test]
+fn octree_distance_matches_the_shape() {
+    let csg_func = sphere(5.0);
+    let octree = render(&csg_func);
+    for &(x, y, z) in &[(0.0, 0.0, 0.0), (1.0, 2.0, -0.5), (4.9, 0.3, 0.1), (-7.0, 7.5, 3.0)] {
+        let distance = query::distance(&octree, Point::new(x, y, z)).unwrap();
+        assert!(
+            (distance - csg_func.call(x, y, z)).abs() < 0.01,
+            "{} at {:?}",
+            distance,
+            (x, y, z)
+        );
+
+        let nearest = query::nearest_point(&octree, Point::new(x, y, z)).unwrap();
+        assert!(csg_func.call_point(nearest).abs() < 0.01);
+    }
+    // Outside of the octree there is still a closest point, but no inside or outside
+    let distance = query::distance(&octree, Point::new(20.0, 0.0, 0.0)).unwrap();
+    assert!((distance - 15.0).abs() < 0.01);
+}
@@ -0,0 +1,44 @@
+use conjure::lang::{self, Error, Reader, Span, SpanTree, Ty};
+
+fn leaf(start: usize, end: usize) -> SpanTree {
+    SpanTree::new(Span::new(start, end), vec![])
+}
+
+#[test]
+fn forms_are_read_with_their_spans() {
+    let source = "; a ball\n(sphere\t[1, 2] \"r\")";
+    let (ty, spans) = Reader::read_spanned(source).unwrap();
+    assert_eq!(ty.to_string(), r#"(sphere [1 2] "r")"#);
+    let vector = SpanTree::new(Span::new(17, 23), vec![leaf(18, 19), leaf(21, 22)]);
+    let expected = SpanTree::new(Span::new(9, 28), vec![leaf(10, 16), vector, leaf(24, 27)]);
+    assert_eq!(spans, expected);
+    assert_eq!(&source[spans.child(1).span.start..spans.child(1).span.end], "[1, 2]");
+}
+
+#[test]
+fn errors_carry_the_span_they_were_found_at() {
+    let span = |source| Reader::read_spanned(source).unwrap_err().span();
+    // The bracket left open
+    assert_eq!(span("(union (sphere 4)\n  (cube [1 2 3]"), Some(Span::new(20, 21)));
+    assert_eq!(span("  "), Some(Span::new(2, 2)));
+    assert_eq!(span("(sphere \"4)"), Some(Span::new(8, 11)));
+
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    let eval = |source| {
+        let (ty, spans) = Reader::read_spanned(source).unwrap();
+        lang::eval_spanned(ty, &spans, &env).unwrap_err()
+    };
+    let error = eval("(let [r 2 s (sphere q)] s)");
+    assert_eq!(error.span(), Some(Span::new(20, 21)));
+    assert!(matches!(error.without_span(), Error::UnknownSymbol(s) if s == "q"));
+    assert_eq!(eval("(union (sphere 1) (scale (sphere 1)))").span(), Some(Span::new(18, 36)));
+
+    // Plain reading and evaluation leave the location out
+    assert!(matches!(Reader::read_str("(sphere").unwrap_err(), Error::FormEarlyEnd(..)));
+    let ty = Reader::read_str("(sphere r)").unwrap();
+    assert!(matches!(lang::eval(ty, &env).unwrap_err(), Error::UnknownSymbol(_)));
+    assert!(matches!(Reader::read_str("4").unwrap(), Ty::Number(n) if n == 4.0));
+}
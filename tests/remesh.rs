@@ -0,0 +1,94 @@
+mod common;
+
+use common::sorted;
+use {
+    conjure::{
+        lang::{self, Ty},
+        mesher::MesherKind,
+        octree::Octree,
+        remesh::Remesher,
+        shape::{self, Change, CsgFunc},
+        types::{Aabb, Point},
+    },
+    std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+fn eval(source: &str) -> CsgFunc {
+    let env = lang::Env::new();
+    for (sym, func) in lang::Namespace::new() {
+        env.register_sym(sym, func);
+    }
+    match lang::eval(lang::Reader::read_str(source).unwrap(), &env).unwrap() {
+        Ty::CsgFunc(csg_func) => Arc::try_unwrap(csg_func).unwrap(),
+        _ => panic!("{} is not a shape", source),
+    }
+}
+
+fn remesher(source: &str, mesher: MesherKind) -> Remesher {
+    Remesher::new(Octree::new(-8.0, 8.0), 0.5, mesher, eval(source))
+}
+
+const BEFORE: &str = "(union (cube [-6 -6 -6] [-3 -3 -3]) (sphere 2))";
+const AFTER: &str = "(union (cube [-6 -6 -6] [-3 -3 -3]) (sphere 2.5))";
+
+#[test]
+fn changes_are_found_by_diffing_the_shapes() {
+    let before = eval(BEFORE);
+    assert_eq!(shape::changed_region(&before, &eval(BEFORE)), Change::Unchanged);
+
+    let sphere = Aabb::new(Point::new(-2.5, -2.5, -2.5), Point::new(2.5, 2.5, 2.5));
+    assert_eq!(shape::changed_region(&before, &eval(AFTER)), Change::Region(sphere));
+
+    // Changes below a scale cover the scaled shape
+    let scaled = shape::changed_region(
+        &eval("(scale 2 (union (cube [0 0 0] [1 1 1]) (sphere 1)))"),
+        &eval("(scale 2 (union (cube [0 0 0] [1 1 2]) (sphere 1)))"),
+    );
+    let both = Aabb::new(Point::new(-2.0, -2.0, -2.0), Point::new(2.0, 2.0, 4.0));
+    assert_eq!(scaled, Change::Region(both));
+
+    // Shapes without a tree can't be narrowed down
+    let opaque = CsgFunc::new(Box::new(|x, y, z| (x * x + y * y + z * z).sqrt() - 2.0));
+    assert_eq!(shape::changed_region(&before, &opaque), Change::Everything);
+}
+
+#[test]
+fn updates_match_a_full_render() {
+    for mesher in [MesherKind::DualContouring, MesherKind::MarchingCubes] {
+        let mut updated = remesher(BEFORE, mesher);
+        assert!(matches!(updated.update(eval(AFTER)), Change::Region(_)));
+        let full = remesher(AFTER, mesher);
+        assert_eq!(sorted(updated.triangles()), sorted(full.triangles()));
+        assert_eq!(updated.octree().leaves().count(), full.octree().leaves().count());
+    }
+}
+
+#[test]
+fn updates_only_sample_the_change() {
+    // Same shape, counting how often it is sampled
+    let counted = |source: &str, calls: Arc<AtomicUsize>| {
+        let inner = eval(source);
+        let (node, bounds) = (inner.node().cloned().unwrap(), inner.bounds());
+        let mut csg_func = CsgFunc::new(Box::new(move |x, y, z| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            inner.call(x, y, z)
+        }));
+        csg_func.set_node(node);
+        csg_func.set_bounds(bounds);
+        csg_func
+    };
+
+    let (full, update) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let mut remesher = Remesher::new(
+        Octree::new(-8.0, 8.0),
+        0.5,
+        MesherKind::DualContouring,
+        counted(BEFORE, full.clone()),
+    );
+    remesher.update(counted(AFTER, update.clone()));
+    let (full, update) = (full.load(Ordering::Relaxed), update.load(Ordering::Relaxed));
+    assert!(update < full / 2, "{} samples to update, {} to render", update, full);
+}
@@ -0,0 +1,24 @@
+use conjure::model::text;
+
+#[test]
+fn text_wraps_to_fit() {
+    assert_eq!(text::wrap("model.cnj:3:4: oops", 10), ["model.cnj:", "3:4: oops"]);
+    assert_eq!(text::wrap("one\n\ntwo", 80), ["one", "", "two"]);
+    assert_eq!(text::wrap("ab", 0), ["a", "b"]);
+}
+
+#[test]
+fn text_is_drawn_where_it_is_put() {
+    let size = [200.0, 100.0];
+    let lines = text::wrap("|-", 80);
+    let rectangles = text::rectangles(&lines, [10.0, 50.0], 2.0, size, [1.0; 3]);
+    // Runs of pixels down a column are drawn at once: one for '|', one per column of '-'
+    assert_eq!(rectangles.len(), 1 + 5);
+    let to_clip = |x: f32, y: f32| [-1.0 + 2.0 * x / size[0], -1.0 + 2.0 * y / size[1]];
+    let (lower, upper) = (to_clip(10.0, 50.0 - 14.0), to_clip(10.0 + 2.0 * 12.0, 50.0));
+    for rectangle in &rectangles {
+        assert!(rectangle.lower[0] >= lower[0] && rectangle.upper[0] <= upper[0]);
+        assert!(rectangle.lower[1] >= lower[1] && rectangle.upper[1] <= upper[1]);
+        assert!(rectangle.lower[0] < rectangle.upper[0] && rectangle.lower[1] < rectangle.upper[1]);
+    }
+}
@@ -0,0 +1,61 @@
+use conjure::{
+    mesh::validate::validate,
+    types::{Point, Triangle},
+};
+
+/// Closed tetrahedron with its faces winding counter-clockwise seen from outside.
+fn tetrahedron(offset: f32) -> Vec<Triangle> {
+    let p = |x: f32, y: f32, z: f32| Point::new(x + offset, y + offset, z + offset);
+    let (a, b, c, d) = (p(0.0, 0.0, 0.0), p(1.0, 0.0, 0.0), p(0.0, 1.0, 0.0), p(0.0, 0.0, 1.0));
+    vec![[a, c, b], [a, b, d], [a, d, c], [b, c, d]]
+}
+
+#[test]
+fn closed_mesh_is_valid() {
+    let report = validate(&tetrahedron(0.0));
+    assert_eq!(report.triangles, 4);
+    assert!(report.is_valid(), "{}", report);
+}
+
+#[test]
+fn holes_and_flips_are_reported() {
+    let mut open = tetrahedron(0.0);
+    open.pop();
+    let report = validate(&open);
+    assert!(!report.is_watertight());
+    assert_eq!(report.boundary_edges.count, 3);
+    assert_eq!(report.boundary_edges.samples.len(), 3);
+
+    let mut flipped = tetrahedron(0.0);
+    flipped[3].swap(1, 2);
+    let report = validate(&flipped);
+    assert!(report.is_watertight());
+    assert_eq!(report.flipped_normals.count, 3);
+}
+
+#[test]
+fn touching_shapes_are_non_manifold() {
+    // Two tetrahedra meeting in a single vertex
+    let mut touching = tetrahedron(0.0);
+    let other: Vec<Triangle> = tetrahedron(0.0)
+        .into_iter()
+        .map(|triangle| triangle.map(|p| Point::new(-p.x, -p.y, -p.z)))
+        .map(|[a, b, c]| [a, c, b])
+        .collect();
+    touching.extend(other);
+    let report = validate(&touching);
+    assert!(report.is_watertight());
+    assert_eq!(report.non_manifold_vertices.count, 1);
+    assert_eq!(report.non_manifold_vertices.samples, vec![Point::new(0.0, 0.0, 0.0)]);
+}
+
+#[test]
+fn crossing_and_degenerate_triangles_are_reported() {
+    let mut mesh = tetrahedron(0.0);
+    mesh.extend(tetrahedron(0.25));
+    mesh.push([Point::new(5.0, 0.0, 0.0), Point::new(6.0, 0.0, 0.0), Point::new(7.0, 0.0, 0.0)]);
+    let report = validate(&mesh);
+    assert!(report.self_intersections.count > 0);
+    assert_eq!(report.degenerate_triangles.count, 1);
+    assert_eq!(report.degenerate_triangles.samples, vec![Point::new(6.0, 0.0, 0.0)]);
+}
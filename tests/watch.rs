@@ -0,0 +1,80 @@
+use {
+    conjure::watch::ModelWatcher,
+    std::{fs, path::PathBuf, time::Duration},
+};
+
+const DELAY: Duration = Duration::from_millis(20);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Empty directory of its own in the temporary directory.
+fn empty_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("conjure-watch-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+#[test]
+fn every_way_of_saving_is_noticed() {
+    let dir = empty_dir("saving");
+    let (model, part) = (dir.join("model.cnj"), dir.join("part.cnj"));
+    fs::write(&model, "(sphere 1)").unwrap();
+    fs::write(&part, "(sphere 2)").unwrap();
+
+    let mut watcher = ModelWatcher::new(DELAY).unwrap();
+    watcher.set_files(&[model.clone(), part.clone()]).unwrap();
+
+    // Written in place
+    fs::write(&part, "(sphere 3)").unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(part.clone()));
+
+    // Renamed over the file
+    fs::write(dir.join(".model.cnj.swp"), "(sphere 4)").unwrap();
+    fs::rename(dir.join(".model.cnj.swp"), &model).unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(model.clone()));
+
+    // Removed and created again
+    fs::remove_file(&model).unwrap();
+    std::thread::sleep(DELAY * 5);
+    fs::write(&model, "(sphere 5)").unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(model.clone()));
+}
+
+#[test]
+fn other_files_are_ignored() {
+    let dir = empty_dir("ignored");
+    let model = dir.join("model.cnj");
+    fs::write(&model, "(sphere 1)").unwrap();
+
+    let mut watcher = ModelWatcher::new(DELAY).unwrap();
+    watcher.set_files(&[model.clone()]).unwrap();
+    fs::write(dir.join("other.cnj"), "(sphere 2)").unwrap();
+    fs::write(dir.join("sub/model.cnj"), "(sphere 2)").unwrap();
+    assert_eq!(watcher.wait_timeout(DELAY * 10), None);
+
+    // Files no longer part of the model stop counting
+    let part = dir.join("sub/part.cnj");
+    fs::write(&part, "(sphere 2)").unwrap();
+    watcher.set_files(&[part.clone()]).unwrap();
+    fs::write(&model, "(sphere 3)").unwrap();
+    fs::write(&part, "(sphere 3)").unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(part));
+}
+
+#[test]
+fn directories_that_fail_are_tried_again() {
+    let dir = empty_dir("failing");
+    let (model, part) = (dir.join("model.cnj"), dir.join("missing/part.cnj"));
+    fs::write(&model, "(sphere 1)").unwrap();
+
+    // The directories that can be watched still are
+    let mut watcher = ModelWatcher::new(DELAY).unwrap();
+    assert!(watcher.set_files(&[model.clone(), part.clone()]).is_err());
+    fs::write(&model, "(sphere 2)").unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(model.clone()));
+
+    fs::create_dir(dir.join("missing")).unwrap();
+    watcher.set_files(&[model.clone(), part.clone()]).unwrap();
+    fs::write(&part, "(sphere 3)").unwrap();
+    assert_eq!(watcher.wait_timeout(TIMEOUT), Some(part));
+}
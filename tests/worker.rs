@@ -0,0 +1,78 @@
+mod common;
+
+use common::{sorted, sphere};
+use {
+    conjure::{
+        mesher::{self, Mesher, MesherKind},
+        octree::Octree,
+        worker::{self, Job, Meshed, Settings},
+    },
+    std::{
+        sync::mpsc::{channel, Receiver, RecvTimeoutError},
+        time::Duration,
+    },
+};
+
+/// Settings for meshing with dual contouring at `resolution`.
+fn settings(resolution: f32) -> Settings {
+    Settings { resolution, bound: 8.0, tolerance: 0.0, mesher: MesherKind::DualContouring }
+}
+
+/// Every mesh the worker sends until it stops.
+fn all_meshes(meshes: Receiver<Meshed>) -> Vec<Meshed> {
+    let mut all = vec![];
+    loop {
+        match meshes.recv_timeout(Duration::from_secs(60)) {
+            Ok(meshed) => all.push(meshed),
+            Err(RecvTimeoutError::Disconnected) => return all,
+            Err(RecvTimeoutError::Timeout) => panic!("the worker is still busy"),
+        }
+    }
+}
+
+/// Triangles of a sphere of `radius` meshed directly at a resolution of 0.5.
+fn sphere_triangles(radius: f32) -> (Octree, Vec<[u32; 9]>) {
+    let mut octree = Octree::new(-8.0, 8.0);
+    octree.render_shape(0.5, &sphere(radius));
+    let faces = MesherKind::DualContouring.extract_faces(&octree);
+    let triangles = sorted(mesher::triangulate(&faces, &sphere(radius)));
+    (octree, triangles)
+}
+
+#[test]
+fn the_latest_job_is_meshed_last() {
+    let (sender, meshes) = channel();
+    let jobs = worker::spawn_with(move |meshed| sender.send(meshed).is_ok(), settings(1.0));
+    for radius in [2.0, 3.0, 4.0] {
+        jobs.send(Job::Shape(sphere(radius))).unwrap();
+    }
+    jobs.send(Job::Resolution(0.5)).unwrap();
+
+    // The worker stops once no one can send it jobs, after finishing the jobs it has
+    drop(jobs);
+    let delivered = all_meshes(meshes);
+
+    // Stale jobs may be skipped, but the last mesh always comes from the last jobs
+    assert!((1..=4).contains(&delivered.len()));
+    let last = delivered.last().unwrap();
+    let (octree, expected) = sphere_triangles(4.0);
+    assert_eq!(sorted(last.triangles.clone()), expected);
+    assert_eq!(last.outlines.len(), octree.leaves().count());
+}
+
+#[test]
+fn stale_jobs_are_cancelled() {
+    // Far too fine to finish within the timeout, unless it is cancelled
+    let (sender, meshes) = channel();
+    let jobs = worker::spawn_with(move |meshed| sender.send(meshed).is_ok(), settings(1.0 / 64.0));
+    jobs.send(Job::Shape(sphere(2.0))).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    jobs.send(Job::Shape(sphere(3.0))).unwrap();
+    jobs.send(Job::Resolution(0.5)).unwrap();
+    drop(jobs);
+
+    // Only the latest shape is meshed
+    let delivered = all_meshes(meshes);
+    assert_eq!(delivered.len(), 1);
+    assert_eq!(sorted(delivered[0].triangles.clone()), sphere_triangles(3.0).1);
+}